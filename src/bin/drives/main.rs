@@ -0,0 +1,317 @@
+//! `drives` lists the block devices with their partitions, sizes, filesystems
+//! and mountpoints, as tree (like `lsblk`), as table or as JSON.
+
+use std::{error::Error, path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use drives::{Device, DeviceFilter, DrivesBuilder, Size};
+
+mod output;
+
+use output::Column;
+
+/// Lists block devices with their partitions, sizes, filesystems and mountpoints
+#[derive(Debug, Parser)]
+#[command(name = "drives", version)]
+struct Args {
+    /// print a flat table instead of the tree
+    #[arg(short, long, conflicts_with = "json")]
+    table: bool,
+    /// print the devices with all details as JSON
+    #[arg(short = 'J', long)]
+    json: bool,
+    /// the columns to print, comma separated
+    #[arg(
+        short,
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "name,size,rm,fstype,mountpoints"
+    )]
+    output: Vec<Column>,
+    /// only list removable devices
+    #[arg(short, long)]
+    removable: bool,
+    /// only list devices that are mounted or have a mounted partition
+    #[arg(short, long)]
+    mounted: bool,
+    /// only list devices of at least this size in bytes, the suffixes K, M, G
+    /// and T are powers of 1024 (e.g. 512M or 1.5T)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    min_size: Option<u64>,
+    /// hide virtual devices like loop, ram and device mapper devices
+    #[arg(long)]
+    no_virtual: bool,
+    /// read the superblocks to detect the filesystems (needs read access to
+    /// the device nodes)
+    #[arg(long)]
+    probe: bool,
+    /// root of the sysfs tree
+    #[arg(long, value_name = "PATH", default_value = "/sys")]
+    sysfs_root: PathBuf,
+    /// root of the procfs tree
+    #[arg(long, value_name = "PATH", default_value = "/proc")]
+    procfs_root: PathBuf,
+    /// directory of the device nodes
+    #[arg(long, value_name = "PATH", default_value = "/dev")]
+    dev_root: PathBuf,
+    /// directory of the udev database
+    #[arg(long, value_name = "PATH", default_value = "/run/udev/data")]
+    udev_data_dir: PathBuf,
+}
+
+// parses a size like 4096, 512K or 1.5T into bytes
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, factor) = match value.char_indices().last() {
+        Some((index, suffix)) if suffix.is_ascii_alphabetic() => {
+            let exponent = match suffix.to_ascii_uppercase() {
+                'K' => 1,
+                'M' => 2,
+                'G' => 3,
+                'T' => 4,
+                _ => return Err(format!("unknown size suffix '{}'", suffix)),
+            };
+            (&value[..index], 1024u64.pow(exponent))
+        }
+        _ => (value, 1),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_err| format!("invalid size '{}'", value))?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(format!("invalid size '{}'", value));
+    }
+    Ok((number * factor as f64) as u64)
+}
+
+fn device_filter(args: &Args) -> DeviceFilter {
+    let mut filter = DeviceFilter::new();
+    if args.removable {
+        filter = filter.removable(true);
+    }
+    if args.mounted {
+        filter = filter.mounted(true);
+    }
+    if let Some(min_size) = args.min_size {
+        filter = filter.min_size(Size::from_bytes(min_size));
+    }
+    filter
+}
+
+fn run(args: &Args) -> Result<String, Box<dyn Error>> {
+    let drives = DrivesBuilder::new()
+        .sysfs_root(&args.sysfs_root)
+        .procfs_root(&args.procfs_root)
+        .dev_root(&args.dev_root)
+        .udev_data_dir(&args.udev_data_dir)
+        .include_virtual(!args.no_virtual)
+        .filter(device_filter(args))
+        .probe_filesystems(args.probe)
+        .build();
+    let mut devices: Vec<Device> = drives.get_devices()?;
+    // sysfs lists the devices in no particular order
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+    for device in devices.iter_mut() {
+        device.partitions.sort_by_key(|partition| partition.number);
+    }
+
+    if args.json {
+        let mut json = serde_json::to_string_pretty(&devices)?;
+        json.push('\n');
+        Ok(json)
+    } else {
+        Ok(output::render(&devices, &args.output, !args.table))
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("drives: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, path::Path};
+
+    use tempfile::{tempdir, TempDir};
+
+    fn create_block_device(sysfs_root: &Path, name: &str, size: u64, removable: bool) -> PathBuf {
+        let device_dir = sysfs_root.join("block").join(name);
+        fs::create_dir_all(&device_dir).unwrap();
+        fs::write(device_dir.join("size"), size.to_string()).unwrap();
+        fs::write(
+            device_dir.join("removable"),
+            if removable { "1" } else { "0" },
+        )
+        .unwrap();
+        device_dir
+    }
+
+    fn create_partition(device_dir: &Path, name: &str, number: u32, size: u64, dev: &str) {
+        let partition_dir = device_dir.join(name);
+        fs::create_dir(&partition_dir).unwrap();
+        fs::write(partition_dir.join("size"), size.to_string()).unwrap();
+        fs::write(partition_dir.join("partition"), number.to_string()).unwrap();
+        fs::write(partition_dir.join("dev"), dev).unwrap();
+    }
+
+    // a fixed nvme drive with a mounted EFI system partition and a usb stick
+    // with a filesystem known to udev
+    fn fake_system() -> TempDir {
+        let root = tempdir().unwrap();
+        let sysfs_root = root.path().join("sys");
+
+        let sda = create_block_device(&sysfs_root, "sda", 30031872, true);
+        fs::create_dir(sda.join("device")).unwrap();
+        fs::write(sda.join("device").join("model"), "Ultra Fit").unwrap();
+        create_partition(&sda, "sda1", 1, 30029824, "8:1");
+
+        let nvme = create_block_device(&sysfs_root, "nvme0n1", 1000215216, false);
+        create_partition(&nvme, "nvme0n1p2", 2, 999162511, "259:2");
+        create_partition(&nvme, "nvme0n1p1", 1, 1050624, "259:1");
+
+        let procfs_root = root.path().join("proc");
+        fs::create_dir_all(procfs_root.join("self")).unwrap();
+        fs::write(
+            procfs_root.join("self").join("mountinfo"),
+            concat!(
+                "26 1 259:2 / / rw,relatime - ext4 /dev/nvme0n1p2 rw\n",
+                "27 26 259:1 / /boot/efi rw,relatime - vfat /dev/nvme0n1p1 rw\n",
+            ),
+        )
+        .unwrap();
+
+        let udev_data_dir = root.path().join("udev");
+        fs::create_dir(&udev_data_dir).unwrap();
+        fs::write(
+            udev_data_dir.join("b259:1"),
+            "E:ID_FS_TYPE=vfat\nE:ID_FS_UUID=4A3B-1C2D\n",
+        )
+        .unwrap();
+        fs::write(
+            udev_data_dir.join("b259:2"),
+            "E:ID_FS_TYPE=ext4\nE:ID_FS_LABEL=root\n",
+        )
+        .unwrap();
+        fs::write(
+            udev_data_dir.join("b8:1"),
+            "E:ID_FS_TYPE=exfat\nE:ID_FS_LABEL=STICK\nE:ID_FS_UUID=64E1-2C39\n",
+        )
+        .unwrap();
+        root
+    }
+
+    fn run_with(root: &TempDir, args: &[&str]) -> String {
+        let root = root.path().to_str().unwrap();
+        let sysfs_root = format!("{}/sys", root);
+        let procfs_root = format!("{}/proc", root);
+        // keep the device nodes of the host out of the tests
+        let dev_root = format!("{}/dev", root);
+        let udev_data_dir = format!("{}/udev", root);
+        let mut arguments = vec![
+            "drives",
+            "--sysfs-root",
+            &sysfs_root,
+            "--procfs-root",
+            &procfs_root,
+            "--dev-root",
+            &dev_root,
+            "--udev-data-dir",
+            &udev_data_dir,
+        ];
+        arguments.extend_from_slice(args);
+        run(&Args::parse_from(arguments)).unwrap()
+    }
+
+    #[test]
+    fn test_tree() {
+        let root = fake_system();
+        assert_eq!(
+            concat!(
+                "NAME             SIZE RM FSTYPE MOUNTPOINTS\n",
+                "nvme0n1     476.94 GB  0\n",
+                "├─nvme0n1p1    513 MB  0 vfat   /boot/efi\n",
+                "└─nvme0n1p2 476.44 GB  0 ext4   /\n",
+                "sda          14.32 GB  1\n",
+                "└─sda1       14.32 GB  1 exfat\n",
+            ),
+            run_with(&root, &[])
+        );
+    }
+
+    #[test]
+    fn test_table_with_columns() {
+        let root = fake_system();
+        assert_eq!(
+            concat!(
+                "NAME      TYPE LABEL UUID      MODEL\n",
+                "nvme0n1   disk\n",
+                "nvme0n1p1 part       4A3B-1C2D\n",
+                "nvme0n1p2 part root\n",
+                "sda       disk                 Ultra Fit\n",
+                "sda1      part STICK 64E1-2C39\n",
+            ),
+            run_with(&root, &["--table", "-o", "name,type,label,uuid,model"])
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let root = fake_system();
+        assert_eq!(
+            concat!(
+                "NAME       SIZE\n",
+                "sda    14.32 GB\n",
+                "└─sda1 14.32 GB\n",
+            ),
+            run_with(&root, &["--removable", "-o", "name,size"])
+        );
+        assert_eq!(
+            "NAME\nnvme0n1\nnvme0n1p1\nnvme0n1p2\n",
+            run_with(&root, &["--table", "--mounted", "-o", "name"])
+        );
+        assert_eq!(
+            "NAME\nnvme0n1\nnvme0n1p1\nnvme0n1p2\n",
+            run_with(&root, &["--table", "--min-size", "100G", "-o", "name"])
+        );
+        assert_eq!(
+            "NAME\n",
+            run_with(&root, &["--mounted", "--removable", "-o", "name"])
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let root = fake_system();
+        let json: serde_json::Value =
+            serde_json::from_str(&run_with(&root, &["--json", "--removable"])).unwrap();
+        let devices = json.as_array().unwrap();
+        assert_eq!(1, devices.len());
+        assert_eq!("sda", devices[0]["name"]);
+        assert_eq!(true, devices[0]["is_removable"]);
+        assert_eq!("STICK", devices[0]["partitions"][0]["udev"]["fs_label"]);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(Ok(4096), parse_size("4096"));
+        assert_eq!(Ok(512 * 1024), parse_size("512K"));
+        assert_eq!(Ok(8 * 1024 * 1024 * 1024), parse_size("8g"));
+        assert_eq!(Ok(1649267441664), parse_size("1.5T"));
+        assert!(parse_size("12X").is_err());
+        assert!(parse_size("big").is_err());
+        assert!(parse_size("-1G").is_err());
+    }
+}
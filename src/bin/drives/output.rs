@@ -0,0 +1,184 @@
+use clap::ValueEnum;
+use drives::{Device, Partition};
+
+/// The columns of the tree and table output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    /// the device or partition name
+    Name,
+    /// the size in a human readable unit
+    Size,
+    /// removable flag (1 or 0)
+    Rm,
+    /// `part` or the kind of the device (`disk`, `loop`, `dm`, `rom`, ...)
+    Type,
+    /// the transport of the device (`nvme`, `sata`, `usb`, `mmc`, ...)
+    Tran,
+    /// the filesystem type
+    Fstype,
+    /// the filesystem label
+    Label,
+    /// the filesystem UUID
+    Uuid,
+    /// the model of the device
+    Model,
+    /// the serial of the device
+    Serial,
+    /// all mountpoints, comma separated
+    Mountpoints,
+}
+
+impl Column {
+    fn header(&self) -> &str {
+        match *self {
+            Column::Name => "NAME",
+            Column::Size => "SIZE",
+            Column::Rm => "RM",
+            Column::Type => "TYPE",
+            Column::Tran => "TRAN",
+            Column::Fstype => "FSTYPE",
+            Column::Label => "LABEL",
+            Column::Uuid => "UUID",
+            Column::Model => "MODEL",
+            Column::Serial => "SERIAL",
+            Column::Mountpoints => "MOUNTPOINTS",
+        }
+    }
+
+    fn is_right_aligned(&self) -> bool {
+        matches!(*self, Column::Size | Column::Rm)
+    }
+}
+
+// a line of the output, either a device or one of its partitions
+struct Row<'a> {
+    tree_prefix: &'static str,
+    device: &'a Device,
+    partition: Option<&'a Partition>,
+}
+
+impl Row<'_> {
+    fn cell(&self, column: Column) -> String {
+        let udev = match self.partition {
+            Some(partition) => partition.udev.as_ref(),
+            None => self.device.udev.as_ref(),
+        };
+        let filesystem = match self.partition {
+            Some(partition) => partition.filesystem.as_ref(),
+            None => self.device.filesystem.as_ref(),
+        };
+        // the superblock wins over the (possibly outdated) udev database
+        let fs_or_udev = |from_filesystem: Option<String>, from_udev: Option<&String>| {
+            from_filesystem
+                .or_else(|| from_udev.cloned())
+                .unwrap_or_default()
+        };
+        match column {
+            Column::Name => {
+                let name = self
+                    .partition
+                    .map_or(&self.device.name, |partition| &partition.name);
+                format!("{}{}", self.tree_prefix, name)
+            }
+            Column::Size => self
+                .partition
+                .map_or(self.device.size, |partition| partition.size)
+                .as_human_readable_string(),
+            Column::Rm => if self.device.is_removable { "1" } else { "0" }.to_string(),
+            Column::Type if self.partition.is_some() => "part".to_string(),
+            Column::Type => self.device.kind.name().to_string(),
+            Column::Tran => self.device.transport.name().to_string(),
+            Column::Fstype => fs_or_udev(
+                filesystem.map(|filesystem| filesystem.fs_type.name().to_string()),
+                udev.and_then(|udev| udev.fs_type.as_ref()),
+            ),
+            Column::Label => fs_or_udev(
+                filesystem.and_then(|filesystem| filesystem.label.clone()),
+                udev.and_then(|udev| udev.fs_label.as_ref()),
+            ),
+            Column::Uuid => fs_or_udev(
+                filesystem.and_then(|filesystem| filesystem.uuid.clone()),
+                udev.and_then(|udev| udev.fs_uuid.as_ref()),
+            ),
+            Column::Model if self.partition.is_none() => {
+                self.device.model.clone().unwrap_or_default()
+            }
+            Column::Serial if self.partition.is_none() => {
+                self.device.serial.clone().unwrap_or_default()
+            }
+            Column::Model | Column::Serial => String::new(),
+            Column::Mountpoints => self
+                .partition
+                .map_or(&self.device.mountpoints, |partition| &partition.mountpoints)
+                .iter()
+                .map(|mount| mount.mountpoint.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+}
+
+fn rows(devices: &[Device], tree: bool) -> Vec<Row<'_>> {
+    let mut rows = vec![];
+    for device in devices {
+        rows.push(Row {
+            tree_prefix: "",
+            device,
+            partition: None,
+        });
+        for (index, partition) in device.partitions.iter().enumerate() {
+            let is_last = index + 1 == device.partitions.len();
+            let tree_prefix = match (tree, is_last) {
+                (false, _) => "",
+                (true, false) => "├─",
+                (true, true) => "└─",
+            };
+            rows.push(Row {
+                tree_prefix,
+                device,
+                partition: Some(partition),
+            });
+        }
+    }
+    rows
+}
+
+/// renders the devices with their partitions as aligned columns, as tree
+/// (partitions below their device) or as flat table
+pub fn render(devices: &[Device], columns: &[Column], tree: bool) -> String {
+    let header: Vec<String> = columns
+        .iter()
+        .map(|column| column.header().to_string())
+        .collect();
+    let mut lines = vec![header];
+    for row in rows(devices, tree) {
+        lines.push(columns.iter().map(|column| row.cell(*column)).collect());
+    }
+
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|index| {
+            lines
+                .iter()
+                .map(|line| line[index].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut output = String::new();
+    for line in lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(columns.iter().zip(&widths))
+            .map(|(cell, (column, width))| {
+                if column.is_right_aligned() {
+                    format!("{:>width$}", cell, width = width)
+                } else {
+                    format!("{:<width$}", cell, width = width)
+                }
+            })
+            .collect();
+        output.push_str(cells.join(" ").trim_end());
+        output.push('\n');
+    }
+    output
+}
@@ -0,0 +1,69 @@
+use std::{fmt, str::FromStr};
+
+/// The major and minor number identifying a device (dev_t)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceNumber {
+    /// the major number, identifying the driver
+    pub major: u32,
+    /// the minor number, identifying the device for the driver
+    pub minor: u32,
+}
+
+impl DeviceNumber {
+    pub fn new(major: u32, minor: u32) -> DeviceNumber {
+        DeviceNumber { major, minor }
+    }
+
+    /// splits a dev_t (e.g. from `st_rdev`) like glibc's `major()` and `minor()`
+    pub fn from_dev_t(dev: u64) -> DeviceNumber {
+        DeviceNumber {
+            major: (((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0x0000_0fff)) as u32,
+            minor: (((dev >> 12) & 0xffff_ff00) | (dev & 0x0000_00ff)) as u32,
+        }
+    }
+}
+
+impl FromStr for DeviceNumber {
+    type Err = ();
+
+    /// parses the `major:minor` notation used by sysfs and mountinfo
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (major, minor) = value.trim().split_once(':').ok_or(())?;
+        Ok(DeviceNumber {
+            major: major.parse().map_err(|_| ())?,
+            minor: minor.parse().map_err(|_| ())?,
+        })
+    }
+}
+
+impl fmt::Display for DeviceNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.major, self.minor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_device_number() {
+        let device_number: DeviceNumber = "259:1".parse().unwrap();
+        assert_eq!(DeviceNumber::new(259, 1), device_number);
+        assert_eq!("259:1", device_number.to_string());
+
+        assert!("259".parse::<DeviceNumber>().is_err());
+        assert!("a:1".parse::<DeviceNumber>().is_err());
+    }
+
+    #[test]
+    fn test_from_dev_t() {
+        assert_eq!(DeviceNumber::new(8, 1), DeviceNumber::from_dev_t(0x801));
+        assert_eq!(DeviceNumber::new(259, 5), DeviceNumber::from_dev_t(0x10305));
+        assert_eq!(
+            DeviceNumber::new(253, 300),
+            DeviceNumber::from_dev_t(0x0010_fd2c)
+        );
+    }
+}
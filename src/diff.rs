@@ -0,0 +1,470 @@
+use crate::{Device, FilesystemInfo, GptUUID, Mount, Partition, Size, UdevInfo};
+
+/// The kind of UUID reported by [`Change::UuidChanged`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UuidKind {
+    /// the UUID of the partition table (GPT disk GUID or MBR disk signature)
+    PartitionTable,
+    /// the UUID of a partition table entry (PARTUUID)
+    Partition,
+    /// the UUID of a filesystem
+    Filesystem,
+}
+
+/// A difference between two lists of devices found by [`diff`]
+///
+/// Devices and partitions are named by their name in the new list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Change {
+    /// a device only found in the new list
+    DeviceAdded(Device),
+    /// a device only found in the old list
+    DeviceRemoved(Device),
+    /// the same device got another kernel name, e.g. `sdb` became `sdc`
+    DeviceRenamed { old: String, new: String },
+    /// a partition only found on the device in the new list
+    PartitionAdded {
+        device: String,
+        partition: Partition,
+    },
+    /// a partition only found on the device in the old list
+    PartitionRemoved {
+        device: String,
+        partition: Partition,
+    },
+    /// the size of a device or partition changed
+    SizeChanged { name: String, old: Size, new: Size },
+    /// a device or partition got mounted at a new location
+    Mounted { name: String, mount: Mount },
+    /// a device or partition is no longer mounted at a location
+    Unmounted { name: String, mount: Mount },
+    /// a UUID of a device or partition changed, appeared or disappeared
+    UuidChanged {
+        name: String,
+        kind: UuidKind,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+// the stable identifiers of a device, not every source fills all of them
+// (e.g. the wwid may come from udev in one snapshot only)
+struct Identity<'a> {
+    wwid: Option<&'a str>,
+    serial: Option<&'a str>,
+    partition_table: Option<String>,
+}
+
+fn identity(device: &Device) -> Identity<'_> {
+    Identity {
+        wwid: device.wwid.as_deref(),
+        serial: device.serial.as_deref(),
+        partition_table: table_uuid(device),
+    }
+}
+
+// Some(true) if an identifier known for both devices is equal, Some(false) if
+// they have identifiers in common but none is equal and None if there is
+// nothing to compare
+fn is_same_device(a: &Identity, b: &Identity) -> Option<bool> {
+    let shared = [
+        (a.wwid, b.wwid),
+        (a.serial, b.serial),
+        (a.partition_table.as_deref(), b.partition_table.as_deref()),
+    ]
+    .into_iter()
+    .filter_map(|(a, b)| Some((a?, b?)))
+    .collect::<Vec<_>>();
+    if shared.is_empty() {
+        None
+    } else {
+        Some(shared.iter().any(|(a, b)| a == b))
+    }
+}
+
+fn table_uuid(device: &Device) -> Option<String> {
+    if let GptUUID::UUID(uuid) = &device.uuid {
+        return Some(uuid.clone());
+    }
+    device
+        .mbr
+        .as_ref()
+        .filter(|mbr| mbr.disk_signature != 0)
+        .map(|mbr| mbr.pt_uuid.clone())
+        .or_else(|| device.udev.as_ref()?.part_table_uuid.clone())
+}
+
+fn partition_uuid(partition: &Partition) -> Option<String> {
+    partition
+        .gpt
+        .as_ref()
+        .map(|gpt| gpt.part_uuid.clone())
+        .or_else(|| partition.mbr.as_ref().map(|mbr| mbr.part_uuid.clone()))
+        .or_else(|| partition.udev.as_ref()?.part_entry_uuid.clone())
+}
+
+fn filesystem_uuid(filesystem: &Option<FilesystemInfo>, udev: &Option<UdevInfo>) -> Option<String> {
+    filesystem
+        .as_ref()
+        .and_then(|filesystem| filesystem.uuid.clone())
+        .or_else(|| udev.as_ref()?.fs_uuid.clone())
+}
+
+// mount ids are not stable between boots, a mount is identified by its location
+fn diff_mountpoints(name: &str, old: &[Mount], new: &[Mount], changes: &mut Vec<Change>) {
+    let is_same = |a: &Mount, b: &Mount| a.mountpoint == b.mountpoint && a.root == b.root;
+    for mount in old {
+        if !new.iter().any(|other| is_same(mount, other)) {
+            changes.push(Change::Unmounted {
+                name: name.to_owned(),
+                mount: mount.clone(),
+            });
+        }
+    }
+    for mount in new {
+        if !old.iter().any(|other| is_same(mount, other)) {
+            changes.push(Change::Mounted {
+                name: name.to_owned(),
+                mount: mount.clone(),
+            });
+        }
+    }
+}
+
+fn diff_value<T: PartialEq>(
+    old: T,
+    new: T,
+    change: impl FnOnce(T, T) -> Change,
+    changes: &mut Vec<Change>,
+) {
+    if old != new {
+        changes.push(change(old, new));
+    }
+}
+
+fn diff_uuid(
+    name: &str,
+    kind: UuidKind,
+    old: Option<String>,
+    new: Option<String>,
+    changes: &mut Vec<Change>,
+) {
+    let change = |old, new| Change::UuidChanged {
+        name: name.to_owned(),
+        kind,
+        old,
+        new,
+    };
+    diff_value(old, new, change, changes);
+}
+
+fn diff_size(name: &str, old: Size, new: Size, changes: &mut Vec<Change>) {
+    let change = |old, new| Change::SizeChanged {
+        name: name.to_owned(),
+        old,
+        new,
+    };
+    diff_value(old, new, change, changes);
+}
+
+// partitions keep their number when the device is renamed, so they are matched by it
+fn diff_partitions(device: &str, old: &[Partition], new: &[Partition], changes: &mut Vec<Change>) {
+    for partition in old {
+        if !new.iter().any(|other| other.number == partition.number) {
+            changes.push(Change::PartitionRemoved {
+                device: device.to_owned(),
+                partition: partition.clone(),
+            });
+        }
+    }
+    for partition in new {
+        let Some(old) = old.iter().find(|other| other.number == partition.number) else {
+            changes.push(Change::PartitionAdded {
+                device: device.to_owned(),
+                partition: partition.clone(),
+            });
+            continue;
+        };
+        let name = &partition.name;
+        diff_size(name, old.size, partition.size, changes);
+        diff_uuid(
+            name,
+            UuidKind::Partition,
+            partition_uuid(old),
+            partition_uuid(partition),
+            changes,
+        );
+        diff_uuid(
+            name,
+            UuidKind::Filesystem,
+            filesystem_uuid(&old.filesystem, &old.udev),
+            filesystem_uuid(&partition.filesystem, &partition.udev),
+            changes,
+        );
+        diff_mountpoints(name, &old.mountpoints, &partition.mountpoints, changes);
+    }
+}
+
+fn diff_device(old: &Device, new: &Device, changes: &mut Vec<Change>) {
+    let name = &new.name;
+    if old.name != new.name {
+        changes.push(Change::DeviceRenamed {
+            old: old.name.clone(),
+            new: new.name.clone(),
+        });
+    }
+    diff_size(name, old.size, new.size, changes);
+    diff_uuid(
+        name,
+        UuidKind::PartitionTable,
+        table_uuid(old),
+        table_uuid(new),
+        changes,
+    );
+    diff_uuid(
+        name,
+        UuidKind::Filesystem,
+        filesystem_uuid(&old.filesystem, &old.udev),
+        filesystem_uuid(&new.filesystem, &new.udev),
+        changes,
+    );
+    diff_mountpoints(name, &old.mountpoints, &new.mountpoints, changes);
+    diff_partitions(name, &old.partitions, &new.partitions, changes);
+}
+
+/// Compares two results of [`crate::get_devices`], e.g. stored inventory snapshots
+///
+/// Devices are the same if one of the stable identifiers known for both is
+/// equal: the WWID, the serial or the UUID of the partition table. Only if
+/// the two devices have none of these in common the kernel name is compared.
+/// Devices sharing an identifier (e.g. the slots of a card reader reporting
+/// the same serial) are matched by name among each other. Partitions are
+/// matched by their number.
+pub fn diff(old: &[Device], new: &[Device]) -> Vec<Change> {
+    let mut unmatched: Vec<&Device> = old.iter().collect();
+    let mut pairs = vec![];
+    let mut added = vec![];
+    for device in new {
+        let key = identity(device);
+        let matches = |old: &&Device, same: Option<bool>, same_name: bool| {
+            is_same_device(&identity(old), &key) == same && (!same_name || old.name == device.name)
+        };
+        let position = unmatched
+            .iter()
+            .position(|old| matches(old, Some(true), true))
+            .or_else(|| {
+                unmatched
+                    .iter()
+                    .position(|old| matches(old, Some(true), false))
+            })
+            .or_else(|| unmatched.iter().position(|old| matches(old, None, true)));
+        match position {
+            Some(position) => pairs.push((unmatched.remove(position), device)),
+            None => added.push(device),
+        }
+    }
+
+    let mut changes: Vec<Change> = unmatched
+        .into_iter()
+        .map(|device| Change::DeviceRemoved(device.clone()))
+        .collect();
+    changes.extend(
+        added
+            .into_iter()
+            .map(|device| Change::DeviceAdded(device.clone())),
+    );
+    for (old, new) in pairs {
+        diff_device(old, new, &mut changes);
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{DeviceNumber, MountOptions};
+
+    fn partition(name: &str, number: u32, size: u64) -> Partition {
+        Partition {
+            size: Size::new(size),
+            ..Partition::fixture(name, number)
+        }
+    }
+
+    fn device(name: &str, serial: Option<&str>, partitions: Vec<Partition>) -> Device {
+        Device {
+            partitions,
+            is_removable: true,
+            serial: serial.map(str::to_string),
+            size: Size::new(30031872),
+            ..Device::fixture(name)
+        }
+    }
+
+    fn mount(mountpoint: &str, mount_id: u32) -> Mount {
+        Mount {
+            device: "/dev/sdb1".to_string(),
+            mountpoint: mountpoint.to_string(),
+            filesystem: "vfat".to_string(),
+            root: "/".to_string(),
+            dev: DeviceNumber::new(8, 17),
+            mount_id,
+            options: MountOptions::default(),
+        }
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let devices = vec![
+            device("sda", Some("S4EWNX0N"), vec![partition("sda1", 1, 2048)]),
+            device("sdb", None, vec![]),
+        ];
+        assert!(diff(&devices, &devices.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_renamed_device() {
+        let mut stick = partition("sdb1", 1, 30029824);
+        stick.mountpoints = vec![mount("/media/stick", 40)];
+        let old = vec![device("sdb", Some("4C530001"), vec![stick])];
+
+        // after a reboot the stick is sdc, the mount id changed as well
+        let mut stick = partition("sdc1", 1, 30029824);
+        stick.mountpoints = vec![mount("/media/stick", 87)];
+        let new = vec![device("sdc", Some("4C530001"), vec![stick])];
+
+        assert_eq!(
+            vec![Change::DeviceRenamed {
+                old: "sdb".to_string(),
+                new: "sdc".to_string()
+            }],
+            diff(&old, &new)
+        );
+    }
+
+    #[test]
+    fn test_diff_changes() {
+        let mut root = partition("sda1", 1, 2048);
+        root.mountpoints = vec![mount("/", 26)];
+        let old = vec![
+            device("sda", Some("S4EWNX0N"), vec![root.clone()]),
+            device("sdb", Some("4C530001"), vec![partition("sdb1", 1, 2048)]),
+        ];
+
+        let mut resized = partition("sda1", 1, 4096);
+        resized.mountpoints = vec![mount("/", 26), mount("/mnt/backup", 52)];
+        resized.filesystem = Some(FilesystemInfo {
+            fs_type: crate::FilesystemType::Ext4,
+            label: None,
+            uuid: Some("3cdd6997-9b47-46f1-a160-49546976c24e".to_string()),
+        });
+        let mut card = device("mmcblk0", Some("0x1234abcd"), vec![]);
+        card.uuid = GptUUID::UUID("f0ce7b2c-74af-47e4-8141-b2fe24ac20cc".to_string());
+        let new = vec![
+            device(
+                "sda",
+                Some("S4EWNX0N"),
+                vec![resized.clone(), partition("sda2", 2, 1024)],
+            ),
+            card.clone(),
+        ];
+
+        let changes = diff(&old, &new);
+        assert_eq!(6, changes.len());
+        assert!(matches!(&changes[0], Change::DeviceRemoved(device) if device.name == "sdb"));
+        assert_eq!(Change::DeviceAdded(card), changes[1]);
+        assert_eq!(
+            Change::SizeChanged {
+                name: "sda1".to_string(),
+                old: Size::new(2048),
+                new: Size::new(4096)
+            },
+            changes[2]
+        );
+        assert_eq!(
+            Change::UuidChanged {
+                name: "sda1".to_string(),
+                kind: UuidKind::Filesystem,
+                old: None,
+                new: Some("3cdd6997-9b47-46f1-a160-49546976c24e".to_string())
+            },
+            changes[3]
+        );
+        assert_eq!(
+            Change::Mounted {
+                name: "sda1".to_string(),
+                mount: mount("/mnt/backup", 52)
+            },
+            changes[4]
+        );
+        assert!(matches!(
+            &changes[5],
+            Change::PartitionAdded { device, partition } if device == "sda" && partition.number == 2
+        ));
+
+        // and back again
+        let changes = diff(&new, &old);
+        assert!(changes.contains(&Change::Unmounted {
+            name: "sda1".to_string(),
+            mount: mount("/mnt/backup", 52)
+        }));
+        assert!(changes.contains(&Change::PartitionRemoved {
+            device: "sda".to_string(),
+            partition: partition("sda2", 2, 1024)
+        }));
+    }
+
+    #[test]
+    fn test_diff_wwid_on_one_side() {
+        // udev provided the wwid only when the new snapshot was taken
+        let old = vec![
+            device("sda", Some("S4EWNX0N"), vec![]),
+            device("sdb", None, vec![]),
+        ];
+        let mut disk = device("sdc", Some("S4EWNX0N"), vec![]);
+        disk.wwid = Some("eui.0025388b71b2f5a4".to_string());
+        let new = vec![disk, device("sdb", None, vec![])];
+        assert_eq!(
+            vec![Change::DeviceRenamed {
+                old: "sda".to_string(),
+                new: "sdc".to_string()
+            }],
+            diff(&old, &new)
+        );
+
+        // another disk at the same name is a different device
+        let mut other = device("sda", Some("WD-WCC4N0"), vec![]);
+        other.wwid = Some("naa.50014ee2b5e0c1a7".to_string());
+        let changes = diff(&old[..1], &[other]);
+        assert_eq!(2, changes.len());
+        assert!(matches!(&changes[0], Change::DeviceRemoved(_)));
+        assert!(matches!(&changes[1], Change::DeviceAdded(_)));
+    }
+
+    #[test]
+    fn test_diff_shared_serial() {
+        // a card reader with two slots reporting the same serial for both
+        let old = vec![
+            device("sdc", Some("058F63666485"), vec![]),
+            device("sdd", Some("058F63666485"), vec![]),
+        ];
+        let mut new = vec![
+            device("sdd", Some("058F63666485"), vec![]),
+            device("sdc", Some("058F63666485"), vec![]),
+        ];
+        assert!(diff(&old, &new).is_empty());
+
+        new[0].size = Size::new(0);
+        assert_eq!(
+            vec![Change::SizeChanged {
+                name: "sdd".to_string(),
+                old: Size::new(30031872),
+                new: Size::new(0)
+            }],
+            diff(&old, &new)
+        );
+    }
+}
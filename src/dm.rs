@@ -0,0 +1,293 @@
+use std::path::Path;
+
+use crate::fs_wrap;
+
+/// The owner of a device mapper device, decoded from the prefix of its UUID
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DmTarget {
+    /// an LVM logical volume (UUID prefix `LVM-`)
+    Lvm {
+        /// the name of the volume group
+        vg_name: String,
+        /// the name of the logical volume
+        lv_name: String,
+        /// the internal layer, e.g. `real` or `cow` for snapshots, `tdata` for thin pools
+        layer: Option<String>,
+        /// the UUID of the volume group as shown by `vgs -o uuid`
+        vg_uuid: String,
+        /// the UUID of the logical volume as shown by `lvs -o uuid`
+        lv_uuid: String,
+    },
+    /// a dm-crypt mapping (UUID prefix `CRYPT-`)
+    Crypt {
+        /// the type: `LUKS1`, `LUKS2`, `PLAIN`, `BITLK`, ...
+        crypt_type: String,
+        /// the UUID of the LUKS header (the UUID blkid shows for the encrypted partition)
+        luks_uuid: Option<String>,
+    },
+    /// a multipath device (UUID prefix `mpath-`)
+    Multipath {
+        /// the WWID of the multipathed disk
+        wwid: String,
+    },
+    /// a partition of another device mapper device, e.g. created by kpartx
+    /// (UUID prefix `part<N>-`)
+    Partition {
+        /// the partition number
+        number: u32,
+        /// the UUID of the device mapper device holding the partition table
+        parent_uuid: String,
+    },
+    /// an owner not known by this crate or a device without UUID
+    Other,
+}
+
+/// Details of a device mapper device from `/sys/block/dm-N/dm`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DmInfo {
+    /// the mapper name, e.g. `vg0-root` or `luks-0b7c6f3e-...`
+    pub name: String,
+    /// the device mapper UUID, its prefix identifies the owner
+    pub uuid: Option<String>,
+    /// the path of the device node below `/dev/mapper`
+    pub mapper_path: String,
+    /// whether the device is suspended, I/O to it blocks until it is resumed
+    pub suspended: bool,
+    /// the decoded owner
+    pub target: DmTarget,
+}
+
+// LVM writes the 32 characters of a UUID in groups of 6-4-4-4-4-4-6
+fn format_lvm_uuid(uuid: &str) -> String {
+    let mut formatted = String::new();
+    let mut rest = uuid;
+    for len in [6, 4, 4, 4, 4, 4, 6] {
+        let (group, remaining) = rest.split_at(len.min(rest.len()));
+        if !formatted.is_empty() {
+            formatted.push('-');
+        }
+        formatted.push_str(group);
+        rest = remaining;
+    }
+    formatted
+}
+
+// 32 hex digits in the usual 8-4-4-4-12 UUID notation
+fn format_uuid(hex: &str) -> String {
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Splits an LVM mapper name like `my--vg-root` into its parts, a dash within
+/// a name is doubled
+pub(crate) fn split_lvm_name(name: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '-' {
+            if chars.peek() == Some(&'-') {
+                chars.next();
+                parts.last_mut().unwrap().push('-');
+            } else {
+                parts.push(String::new());
+            }
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    parts
+}
+
+fn decode_lvm(name: &str, uuid: &str) -> Option<DmTarget> {
+    let ids = uuid.split('-').next()?;
+    // the ids are sliced by byte offsets below
+    if ids.len() != 64 || !ids.is_ascii() {
+        return None;
+    }
+    let mut parts = split_lvm_name(name).into_iter();
+    Some(DmTarget::Lvm {
+        vg_name: parts.next()?,
+        lv_name: parts.next()?,
+        layer: parts.next(),
+        vg_uuid: format_lvm_uuid(&ids[..32]),
+        lv_uuid: format_lvm_uuid(&ids[32..]),
+    })
+}
+
+fn decode_crypt(uuid: &str) -> Option<DmTarget> {
+    let (crypt_type, rest) = uuid.split_once('-')?;
+    // LUKS has the header UUID without dashes in front of the mapper name
+    let luks_uuid = if crypt_type.starts_with("LUKS") {
+        rest.get(..32)
+            .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(format_uuid)
+    } else {
+        None
+    };
+    Some(DmTarget::Crypt {
+        crypt_type: crypt_type.to_string(),
+        luks_uuid,
+    })
+}
+
+fn decode_partition(uuid: &str) -> Option<DmTarget> {
+    let (prefix, parent_uuid) = uuid.split_once('-')?;
+    Some(DmTarget::Partition {
+        number: prefix.strip_prefix("part")?.parse().ok()?,
+        parent_uuid: parent_uuid.to_string(),
+    })
+}
+
+/// Decodes the owner from the device mapper UUID
+pub(crate) fn decode_target(name: &str, uuid: Option<&str>) -> DmTarget {
+    let Some(uuid) = uuid else {
+        return DmTarget::Other;
+    };
+    let target = if let Some(ids) = uuid.strip_prefix("LVM-") {
+        decode_lvm(name, ids)
+    } else if let Some(rest) = uuid.strip_prefix("CRYPT-") {
+        decode_crypt(rest)
+    } else if let Some(wwid) = uuid.strip_prefix("mpath-") {
+        Some(DmTarget::Multipath {
+            wwid: wwid.to_string(),
+        })
+    } else if uuid.starts_with("part") {
+        decode_partition(uuid)
+    } else {
+        None
+    };
+    target.unwrap_or(DmTarget::Other)
+}
+
+/// Reads `<device_dir>/dm`, None if the device is no device mapper device
+pub(crate) fn read_dm_info(device_dir: &Path, dev_path: &Path) -> Option<DmInfo> {
+    let dm_dir = device_dir.join("dm");
+    let name = fs_wrap::read_file_to_string(&dm_dir.join("name")).ok()?;
+    let uuid = fs_wrap::read_file_to_string(&dm_dir.join("uuid"))
+        .ok()
+        .filter(|uuid| !uuid.is_empty());
+    let suspended = fs_wrap::read_bool_file(&fs_wrap::path_to_string(&dm_dir.join("suspended")))
+        .unwrap_or(false);
+    Some(DmInfo {
+        mapper_path: fs_wrap::path_to_string(&dev_path.join("mapper").join(&name)),
+        target: decode_target(&name, uuid.as_deref()),
+        name,
+        uuid,
+        suspended,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_split_lvm_name() {
+        assert_eq!(vec!["vg0", "root"], split_lvm_name("vg0-root"));
+        assert_eq!(vec!["my-vg", "home-lv"], split_lvm_name("my--vg-home--lv"));
+        assert_eq!(vec!["vg0", "snap", "cow"], split_lvm_name("vg0-snap-cow"));
+    }
+
+    #[test]
+    fn test_decode_lvm() {
+        let target = decode_target(
+            "my--vg-root",
+            Some("LVM-Wq1OBBoDnOOKeYH8Vw4AhVPNEk9SQp2dS7vDw2hcLmkS8gBbkh8v9dlJTGsMfvVd"),
+        );
+        assert_eq!(
+            DmTarget::Lvm {
+                vg_name: "my-vg".to_string(),
+                lv_name: "root".to_string(),
+                layer: None,
+                vg_uuid: "Wq1OBB-oDnO-OKeY-H8Vw-4AhV-PNEk-9SQp2d".to_string(),
+                lv_uuid: "S7vDw2-hcLm-kS8g-Bbkh-8v9d-lJTG-sMfvVd".to_string(),
+            },
+            target
+        );
+        // a broken uuid is not decoded
+        assert_eq!(DmTarget::Other, decode_target("vg0-root", Some("LVM-abc")));
+        // 64 bytes, but the 32nd byte is within a multibyte character
+        let uuid = format!("LVM-{}é{}", "a".repeat(31), "b".repeat(31));
+        assert_eq!(DmTarget::Other, decode_target("vg0-root", Some(&uuid)));
+    }
+
+    #[test]
+    fn test_decode_crypt() {
+        assert_eq!(
+            DmTarget::Crypt {
+                crypt_type: "LUKS2".to_string(),
+                luks_uuid: Some("0b7c6f3e-5a1d-4e2f-9c8b-7a6d5e4f3c2b".to_string()),
+            },
+            decode_target(
+                "luks-0b7c6f3e-5a1d-4e2f-9c8b-7a6d5e4f3c2b",
+                Some("CRYPT-LUKS2-0b7c6f3e5a1d4e2f9c8b7a6d5e4f3c2b-luks-0b7c6f3e-5a1d-4e2f-9c8b-7a6d5e4f3c2b")
+            )
+        );
+        assert_eq!(
+            DmTarget::Crypt {
+                crypt_type: "PLAIN".to_string(),
+                luks_uuid: None,
+            },
+            decode_target("swap", Some("CRYPT-PLAIN-swap"))
+        );
+    }
+
+    #[test]
+    fn test_decode_multipath_and_partition() {
+        assert_eq!(
+            DmTarget::Multipath {
+                wwid: "3600508b400105e210000900000490000".to_string()
+            },
+            decode_target("mpatha", Some("mpath-3600508b400105e210000900000490000"))
+        );
+        assert_eq!(
+            DmTarget::Partition {
+                number: 1,
+                parent_uuid: "mpath-3600508b400105e210000900000490000".to_string()
+            },
+            decode_target(
+                "mpatha1",
+                Some("part1-mpath-3600508b400105e210000900000490000")
+            )
+        );
+        assert_eq!(
+            DmTarget::Other,
+            decode_target("docker-thinpool", Some("docker-8:1"))
+        );
+        assert_eq!(DmTarget::Other, decode_target("test", None));
+    }
+
+    #[test]
+    fn test_read_dm_info() {
+        let temp_dir = tempdir().unwrap();
+        let dm_dir = temp_dir.path().join("dm-1").join("dm");
+        fs::create_dir_all(&dm_dir).unwrap();
+        fs::write(dm_dir.join("name"), "vg0-root\n").unwrap();
+        fs::write(
+            dm_dir.join("uuid"),
+            "LVM-Wq1OBBoDnOOKeYH8Vw4AhVPNEk9SQp2dS7vDw2hcLmkS8gBbkh8v9dlJTGsMfvVd\n",
+        )
+        .unwrap();
+        fs::write(dm_dir.join("suspended"), "1\n").unwrap();
+
+        let info = read_dm_info(&temp_dir.path().join("dm-1"), Path::new("/dev")).unwrap();
+        assert_eq!("vg0-root", info.name);
+        assert_eq!("/dev/mapper/vg0-root", info.mapper_path);
+        assert!(info.suspended);
+        assert!(matches!(info.target, DmTarget::Lvm { ref lv_name, .. } if lv_name == "root"));
+
+        assert_eq!(None, read_dm_info(temp_dir.path(), Path::new("/dev")));
+    }
+}
@@ -18,6 +18,14 @@ pub enum DrivesError {
     ConversionToU32Failed,
     #[error("failed to access directory {directory:?}")]
     DiraccessError { directory: String },
-    #[error("reading mounts from /proc/mounts failed")]
+    #[error("reading mounts from /proc/self/mountinfo failed")]
     ReadingMountsFailed,
+    #[error("failed to get filesystem statistics for {path:?}")]
+    StatvfsFailed { path: String },
+    #[error("failed to read the superblock while probing for a filesystem")]
+    ProbeFailed,
+    #[error("failed to read the partition table")]
+    ReadingPartitionTableFailed,
+    #[error("failed to listen for block device events")]
+    MonitorFailed,
 }
@@ -0,0 +1,199 @@
+use crate::{Device, Mount, Partition, Size};
+
+/// Criteria selecting the devices returned by [`Drives::get_devices`](crate::Drives::get_devices)
+///
+/// All criteria that are set must match. The filter is evaluated while walking
+/// the sysfs tree, as soon as the needed information is known: a device that
+/// doesn't match is skipped before its udev data, serial, GPT or MBR are read
+/// and before its filesystems are probed.
+///
+/// ```no_run
+/// let filter = drives::DeviceFilter::new()
+///     .removable(true)
+///     .exclude_name_prefixes(["loop", "ram", "zram"])
+///     .non_empty()
+///     .min_size(drives::Size::from_bytes(8 * 1024 * 1024 * 1024));
+/// let devices = drives::DrivesBuilder::new()
+///     .filter(filter)
+///     .build()
+///     .get_devices()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceFilter {
+    name_prefixes: Vec<String>,
+    excluded_name_prefixes: Vec<String>,
+    removable: Option<bool>,
+    mounted: Option<bool>,
+    min_size: Option<Size>,
+    max_size: Option<Size>,
+}
+
+impl DeviceFilter {
+    /// creates a filter matching all devices
+    pub fn new() -> DeviceFilter {
+        DeviceFilter::default()
+    }
+
+    /// only devices whose name starts with one of the given prefixes (e.g. `sd`, `mmcblk`)
+    pub fn name_prefixes<I, S>(mut self, prefixes: I) -> DeviceFilter
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.name_prefixes
+            .extend(prefixes.into_iter().map(Into::into));
+        self
+    }
+
+    /// skips devices whose name starts with one of the given prefixes (e.g. `loop`, `ram`, `zram`)
+    pub fn exclude_name_prefixes<I, S>(mut self, prefixes: I) -> DeviceFilter
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.excluded_name_prefixes
+            .extend(prefixes.into_iter().map(Into::into));
+        self
+    }
+
+    /// only removable (true) or only fixed (false) devices
+    pub fn removable(mut self, removable: bool) -> DeviceFilter {
+        self.removable = Some(removable);
+        self
+    }
+
+    /// only devices that are mounted or have a mounted partition (true) or only
+    /// devices without any mount (false)
+    pub fn mounted(mut self, mounted: bool) -> DeviceFilter {
+        self.mounted = Some(mounted);
+        self
+    }
+
+    /// only devices with at least the given size
+    pub fn min_size(mut self, size: Size) -> DeviceFilter {
+        self.min_size = Some(size);
+        self
+    }
+
+    /// only devices with at most the given size
+    pub fn max_size(mut self, size: Size) -> DeviceFilter {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// skips devices with a size of zero, e.g. card readers without a card
+    pub fn non_empty(self) -> DeviceFilter {
+        let min_size = self.min_size.unwrap_or(Size::new(0)).max(Size::new(1));
+        self.min_size(min_size)
+    }
+
+    /// whether the device matches all criteria, for filtering devices that
+    /// were not read with this filter
+    pub fn matches(&self, device: &Device) -> bool {
+        self.matches_name(&device.name)
+            && self.matches_removable(device.is_removable)
+            && self.matches_size(device.size)
+            && self.matches_mounted(is_mounted(&device.mountpoints, &device.partitions))
+    }
+
+    pub(crate) fn matches_name(&self, name: &str) -> bool {
+        (self.name_prefixes.is_empty()
+            || self
+                .name_prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str())))
+            && !self
+                .excluded_name_prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str()))
+    }
+
+    pub(crate) fn matches_removable(&self, removable: bool) -> bool {
+        self.removable.is_none_or(|wanted| wanted == removable)
+    }
+
+    pub(crate) fn matches_size(&self, size: Size) -> bool {
+        self.min_size.is_none_or(|min_size| size >= min_size)
+            && self.max_size.is_none_or(|max_size| size <= max_size)
+    }
+
+    pub(crate) fn matches_mounted(&self, mounted: bool) -> bool {
+        self.mounted.is_none_or(|wanted| wanted == mounted)
+    }
+}
+
+/// whether a device with these mountpoints or one of its partitions is mounted
+pub(crate) fn is_mounted(mountpoints: &[Mount], partitions: &[Partition]) -> bool {
+    !mountpoints.is_empty()
+        || partitions
+            .iter()
+            .any(|partition| !partition.mountpoints.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{DeviceNumber, MountOptions};
+
+    fn device(name: &str, removable: bool, size: u64, mounted: bool) -> Device {
+        let mountpoints = if mounted {
+            vec![Mount {
+                device: format!("/dev/{}1", name),
+                mountpoint: "/media/stick".to_string(),
+                filesystem: "vfat".to_string(),
+                root: "/".to_string(),
+                dev: DeviceNumber::new(8, 1),
+                mount_id: 40,
+                options: MountOptions::parse("rw"),
+            }]
+        } else {
+            vec![]
+        };
+        Device {
+            partitions: vec![Partition {
+                size: Size::new(size),
+                mountpoints,
+                ..Partition::fixture(&format!("{}1", name), 1)
+            }],
+            is_removable: removable,
+            size: Size::new(size),
+            ..Device::fixture(name)
+        }
+    }
+
+    #[test]
+    fn test_empty_filter_matches_all() {
+        let filter = DeviceFilter::new();
+        assert!(filter.matches(&device("sda", false, 0, false)));
+        assert!(filter.matches(&device("loop0", true, 2048, true)));
+    }
+
+    #[test]
+    fn test_combined_criteria() {
+        let filter = DeviceFilter::new()
+            .name_prefixes(["sd", "mmcblk"])
+            .exclude_name_prefixes(["sdz"])
+            .removable(true)
+            .mounted(false)
+            .non_empty()
+            .max_size(Size::new(4096));
+
+        assert!(filter.matches(&device("sdb", true, 2048, false)));
+        assert!(filter.matches(&device("mmcblk0", true, 4096, false)));
+        assert!(!filter.matches(&device("loop0", true, 2048, false)));
+        assert!(!filter.matches(&device("sdz", true, 2048, false)));
+        assert!(!filter.matches(&device("sdb", false, 2048, false)));
+        assert!(!filter.matches(&device("sdb", true, 2048, true)));
+        assert!(!filter.matches(&device("sdb", true, 0, false)));
+        assert!(!filter.matches(&device("sdb", true, 8192, false)));
+    }
+
+    #[test]
+    fn test_non_empty_keeps_larger_min_size() {
+        let filter = DeviceFilter::new().min_size(Size::new(2048)).non_empty();
+        assert!(!filter.matches(&device("sdb", true, 1024, false)));
+        assert!(filter.matches(&device("sdb", true, 2048, false)));
+    }
+}
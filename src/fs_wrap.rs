@@ -3,7 +3,7 @@
  * implementing struct to be able to mock it with 'mockall'.
  *
  */
-use crate::error::DrivesError;
+use crate::{device_number::DeviceNumber, error::DrivesError};
 use std::{
     fs::{self, DirEntry, File, ReadDir},
     io::{self, BufRead, Read},
@@ -46,7 +46,10 @@ pub fn read_file_to_string(path: &Path) -> Result<String, DrivesError> {
     })?;
 
     let mut result = String::new();
-    file.read_to_string(&mut result);
+    file.read_to_string(&mut result)
+        .map_err(|_err| DrivesError::FileReadError {
+            filename: path_to_string(path),
+        })?;
     result = result.replace('\n', "").trim().to_owned();
     Ok(result)
 }
@@ -73,6 +76,10 @@ pub fn read_file_to_u32(path: &str) -> Result<u32, DrivesError> {
     Ok(size_as_u32)
 }
 
+pub fn read_dev_file(path: &str) -> Option<DeviceNumber> {
+    read_file_to_string(Path::new(path)).ok()?.parse().ok()
+}
+
 pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
 where
     P: AsRef<Path>,
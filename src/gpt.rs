@@ -1,20 +1,24 @@
-use crate::Device;
-#[cfg(feature = "gpt")]
-use gpt;
+use std::{collections::BTreeMap, path::Path};
 
-#[cfg(not(test))]
-const DEV_DIR: &str = "/dev/";
-
-#[cfg(test)]
-const DEV_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources", "/test");
+use crate::Device;
 
 /// Enumeration for holding the gpt UUID or a reason why it is not available
-#[derive(Debug)]
+///
+/// With the feature "serde" it is (de)serialized as tagged enum, e.g.
+/// `{"status": "uuid", "value": "f0ce7b2c-..."}` or `{"status": "not_available"}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "status", content = "value", rename_all = "snake_case")
+)]
 pub enum GptUUID {
-    /// an io error happened when opening the device for read access
-    #[cfg(feature = "gpt")]
-    IoError(::gpt::GptError),
+    /// an io error happened when opening the device for read access, holds
+    /// the message of the error (only set with the feature "gpt", but always
+    /// present so the serialized schema doesn't depend on the features)
+    IoError(String),
     /// the UUID from the partition table (gpt) as a hyphenated string
+    #[cfg_attr(feature = "serde", serde(rename = "uuid"))]
     UUID(String),
     /// the feature "gpt" was not enabled
     FeatureNotEnabled,
@@ -22,22 +26,193 @@ pub enum GptUUID {
     NotAvailable,
 }
 
+/// Well-known partition types identified by the partition type GUID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GptPartitionType {
+    /// EFI System Partition (ESP)
+    EfiSystem,
+    /// BIOS boot partition used by GRUB on GPT disks
+    BiosBoot,
+    /// Microsoft basic data (FAT, exFAT and NTFS data partitions)
+    MicrosoftBasicData,
+    /// Microsoft reserved partition (MSR)
+    MicrosoftReserved,
+    /// Linux filesystem data
+    LinuxFilesystem,
+    /// Linux swap
+    LinuxSwap,
+    /// Linux LVM physical volume
+    LinuxLvm,
+    /// Linux software RAID member
+    LinuxRaid,
+    /// Apple APFS container
+    AppleApfs,
+    /// a type GUID not known by this crate
+    Unknown,
+}
+
+impl GptPartitionType {
+    const KNOWN_TYPES: [(&'static str, GptPartitionType); 9] = [
+        (
+            "C12A7328-F81F-11D2-BA4B-00A0C93EC93B",
+            GptPartitionType::EfiSystem,
+        ),
+        (
+            "21686148-6449-6E6F-744E-656564454649",
+            GptPartitionType::BiosBoot,
+        ),
+        (
+            "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7",
+            GptPartitionType::MicrosoftBasicData,
+        ),
+        (
+            "E3C9E316-0B5C-4DB8-817D-F92DF00215AE",
+            GptPartitionType::MicrosoftReserved,
+        ),
+        (
+            "0FC63DAF-8483-4772-8E79-3D69D8477DE4",
+            GptPartitionType::LinuxFilesystem,
+        ),
+        (
+            "0657FD6D-A4AB-43C4-84E5-0933C84B4F4F",
+            GptPartitionType::LinuxSwap,
+        ),
+        (
+            "E6D6D379-F507-44C2-A23C-238F2A3DF928",
+            GptPartitionType::LinuxLvm,
+        ),
+        (
+            "A19D880F-05FC-4D3B-A006-743F0F84911E",
+            GptPartitionType::LinuxRaid,
+        ),
+        (
+            "7C3457EF-0000-11AA-AA11-00306543ECAC",
+            GptPartitionType::AppleApfs,
+        ),
+    ];
+
+    /// maps a partition type GUID (in any case) to a well-known type
+    pub fn from_guid(type_guid: &str) -> GptPartitionType {
+        GptPartitionType::KNOWN_TYPES
+            .iter()
+            .find(|(guid, _)| guid.eq_ignore_ascii_case(type_guid))
+            .map(|(_, partition_type)| *partition_type)
+            .unwrap_or(GptPartitionType::Unknown)
+    }
+
+    /// a human readable name of the type
+    pub fn name(&self) -> &str {
+        match *self {
+            GptPartitionType::EfiSystem => "EFI System",
+            GptPartitionType::BiosBoot => "BIOS boot",
+            GptPartitionType::MicrosoftBasicData => "Microsoft basic data",
+            GptPartitionType::MicrosoftReserved => "Microsoft reserved",
+            GptPartitionType::LinuxFilesystem => "Linux filesystem",
+            GptPartitionType::LinuxSwap => "Linux swap",
+            GptPartitionType::LinuxLvm => "Linux LVM",
+            GptPartitionType::LinuxRaid => "Linux RAID",
+            GptPartitionType::AppleApfs => "Apple APFS",
+            GptPartitionType::Unknown => "unknown",
+        }
+    }
+}
+
+/// The attribute flags of a GPT partition entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GptAttributes {
+    /// required for the platform to function (bit 0)
+    pub required: bool,
+    /// the firmware must not provide block IO for the partition (bit 1)
+    pub no_block_io: bool,
+    /// legacy BIOS bootable (bit 2)
+    pub legacy_bios_bootable: bool,
+    /// read-only (bit 60, Microsoft basic data)
+    pub read_only: bool,
+    /// hidden (bit 62, Microsoft basic data)
+    pub hidden: bool,
+    /// no drive letter / not automounted (bit 63, Microsoft basic data)
+    pub no_automount: bool,
+    /// all 64 attribute bits as stored in the partition entry
+    pub raw: u64,
+}
+
+impl GptAttributes {
+    pub fn from_flags(flags: u64) -> GptAttributes {
+        let is_set = |bit: u32| flags & (1 << bit) != 0;
+        GptAttributes {
+            required: is_set(0),
+            no_block_io: is_set(1),
+            legacy_bios_bootable: is_set(2),
+            read_only: is_set(60),
+            hidden: is_set(62),
+            no_automount: is_set(63),
+            raw: flags,
+        }
+    }
+}
+
+/// The entry of a partition in the GUID partition table
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GptInfo {
+    /// the unique partition GUID (PARTUUID) as a hyphenated string
+    pub part_uuid: String,
+    /// the partition type GUID as a hyphenated string
+    pub type_guid: String,
+    /// the well-known type the type GUID maps to
+    pub partition_type: GptPartitionType,
+    /// the partition name (PARTLABEL)
+    pub name: String,
+    /// the first logical block of the partition
+    pub first_lba: u64,
+    /// the last logical block of the partition (inclusive)
+    pub last_lba: u64,
+    /// the attribute flags
+    pub attributes: GptAttributes,
+}
+
+#[cfg(feature = "gpt")]
+impl From<&gpt::partition::Partition> for GptInfo {
+    fn from(partition: &gpt::partition::Partition) -> Self {
+        let type_guid = partition.part_type_guid.guid.as_hyphenated().to_string();
+        GptInfo {
+            part_uuid: partition.part_guid.as_hyphenated().to_string(),
+            partition_type: GptPartitionType::from_guid(&type_guid),
+            type_guid,
+            name: partition.name.clone(),
+            first_lba: partition.first_lba,
+            last_lba: partition.last_lba,
+            attributes: GptAttributes::from_flags(partition.flags),
+        }
+    }
+}
+
 // when the feature "gpt" is not enabled this function is used
 // to set the GptUUID::FeatureNotEnabled value
 #[cfg(not(feature = "gpt"))]
-pub fn enrich_with_gpt_uuid(mut device: Device) -> Device {
+pub fn enrich_with_gpt_uuid(mut device: Device, _dev_dir: &Path, _sector_size: u64) -> Device {
     device.uuid = GptUUID::FeatureNotEnabled;
     device
 }
 
 // When the feature "gpt" is enabled then this function will actually read the
-// partition table (gpt) to get the UUID for the device and the partitions
+// partition table (gpt) to get the UUID for the device and the partition entries
 #[cfg(feature = "gpt")]
-pub fn enrich_with_gpt_uuid(mut device: Device) -> Device {
-    let diskpath = std::path::Path::new(DEV_DIR).join(device.name.to_string());
-    let cfg = gpt::GptConfig::new().writable(false);
+pub fn enrich_with_gpt_uuid(mut device: Device, dev_dir: &Path, sector_size: u64) -> Device {
+    let Some(lb_size) = logical_block_size(sector_size) else {
+        device.uuid = GptUUID::NotAvailable;
+        return device;
+    };
+    let diskpath = dev_dir.join(&device.name);
+    let cfg = gpt::GptConfig::new()
+        .writable(false)
+        .logical_block_size(lb_size);
     match cfg.open(diskpath) {
-        Err(error) => device.uuid = GptUUID::IoError(error),
+        // neither the primary nor the backup header is valid, e.g. an MBR-only or blank disk
+        Err(gpt::GptError::Header(_)) => device.uuid = GptUUID::NotAvailable,
+        Err(error) => device.uuid = GptUUID::IoError(error.to_string()),
         Ok(disk) => {
             match disk.primary_header() {
                 Err(_) => device.uuid = GptUUID::NotAvailable,
@@ -46,13 +221,7 @@ pub fn enrich_with_gpt_uuid(mut device: Device) -> Device {
                 }
             };
             for partition in device.partitions.iter_mut() {
-                match disk.partitions().get(&partition.number) {
-                    Some(gpt_partition) => {
-                        partition.part_uuid =
-                            GptUUID::UUID(gpt_partition.part_guid.as_hyphenated().to_string())
-                    }
-                    None => partition.part_uuid = GptUUID::NotAvailable,
-                }
+                partition.gpt = disk.partitions().get(&partition.number).map(GptInfo::from);
             }
         }
     };
@@ -60,65 +229,181 @@ pub fn enrich_with_gpt_uuid(mut device: Device) -> Device {
     device
 }
 
+// the gpt crate only supports 512 and 4096 byte sectors, with any other sector
+// size (e.g. 2048 of optical media) the table can't be read and is reported as
+// not available without opening the device
+#[cfg(feature = "gpt")]
+fn logical_block_size(sector_size: u64) -> Option<gpt::disk::LogicalBlockSize> {
+    match sector_size {
+        512 => Some(gpt::disk::LogicalBlockSize::Lb512),
+        4096 => Some(gpt::disk::LogicalBlockSize::Lb4096),
+        _ => None,
+    }
+}
+
+// without the feature "gpt" the GUID partition table of an image can't be read
+#[cfg(not(feature = "gpt"))]
+pub fn read_image_gpt(_path: &Path, _sector_size: u64) -> (GptUUID, BTreeMap<u32, GptInfo>) {
+    (GptUUID::FeatureNotEnabled, BTreeMap::new())
+}
+
+// reads the disk GUID and the used partition entries (by partition number) from
+// the GUID partition table of an image file
+#[cfg(feature = "gpt")]
+pub fn read_image_gpt(path: &Path, sector_size: u64) -> (GptUUID, BTreeMap<u32, GptInfo>) {
+    let Some(lb_size) = logical_block_size(sector_size) else {
+        return (GptUUID::NotAvailable, BTreeMap::new());
+    };
+    let cfg = gpt::GptConfig::new()
+        .writable(false)
+        .logical_block_size(lb_size);
+    match cfg.open(path) {
+        // neither the primary nor the backup header is valid
+        Err(gpt::GptError::Header(_)) => (GptUUID::NotAvailable, BTreeMap::new()),
+        Err(error) => (GptUUID::IoError(error.to_string()), BTreeMap::new()),
+        Ok(disk) => {
+            let partitions = disk
+                .partitions()
+                .iter()
+                .filter(|(_, partition)| partition.is_used())
+                .map(|(number, partition)| (*number, GptInfo::from(partition)))
+                .collect();
+            (
+                GptUUID::UUID(disk.guid().as_hyphenated().to_string()),
+                partitions,
+            )
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_partition_type_from_guid() {
+        assert_eq!(
+            GptPartitionType::EfiSystem,
+            GptPartitionType::from_guid("c12a7328-f81f-11d2-ba4b-00a0c93ec93b")
+        );
+        assert_eq!(
+            GptPartitionType::LinuxSwap,
+            GptPartitionType::from_guid("0657FD6D-A4AB-43C4-84E5-0933C84B4F4F")
+        );
+        assert_eq!(
+            GptPartitionType::Unknown,
+            GptPartitionType::from_guid("00000000-0000-0000-0000-000000000000")
+        );
+        assert_eq!("Linux RAID", GptPartitionType::LinuxRaid.name());
+    }
+
+    #[test]
+    fn test_attributes_from_flags() {
+        let attributes = GptAttributes::from_flags(0);
+        assert_eq!(GptAttributes::default(), attributes);
+
+        let attributes = GptAttributes::from_flags(1 | 1 << 2 | 1 << 60 | 1 << 62 | 1 << 63);
+        assert!(attributes.required);
+        assert!(!attributes.no_block_io);
+        assert!(attributes.legacy_bios_bootable);
+        assert!(attributes.read_only);
+        assert!(attributes.hidden);
+        assert!(attributes.no_automount);
+        assert_eq!(0xd000_0000_0000_0005, attributes.raw);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_schema() {
+        let uuid = GptUUID::UUID("f0ce7b2c-74af-47e4-8141-b2fe24ac20cc".to_string());
+        let json = serde_json::to_string(&uuid).unwrap();
+        assert_eq!(
+            r#"{"status":"uuid","value":"f0ce7b2c-74af-47e4-8141-b2fe24ac20cc"}"#,
+            json
+        );
+        assert_eq!(uuid, serde_json::from_str(&json).unwrap());
+
+        // also readable by a build without the feature "gpt"
+        let error = GptUUID::IoError("Permission denied (os error 13)".to_string());
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            r#"{"status":"io_error","value":"Permission denied (os error 13)"}"#,
+            json
+        );
+        assert_eq!(error, serde_json::from_str(&json).unwrap());
+
+        let json = serde_json::to_string(&GptUUID::FeatureNotEnabled).unwrap();
+        assert_eq!(r#"{"status":"feature_not_enabled"}"#, json);
+        assert_eq!(
+            GptUUID::NotAvailable,
+            serde_json::from_str(r#"{"status":"not_available"}"#).unwrap()
+        );
+    }
+
     #[cfg(feature = "gpt")]
     #[test]
     fn test_enrich_with_gpt_uuid() {
         use crate::{Partition, Size};
 
         let partition1 = Partition {
-            name: "sda1".to_string(),
             size: Size::new(512),
-            number: 1,
-            mountpoint: None,
-            part_uuid: GptUUID::NotAvailable,
+            ..Partition::fixture("sda1", 1)
         };
         let partition2 = Partition {
-            name: "sda2".to_string(),
             size: Size::new(512),
-            number: 2,
-            mountpoint: None,
-            part_uuid: GptUUID::NotAvailable,
+            ..Partition::fixture("sda2", 2)
         };
 
         let mut device = Device {
-            name: "gptdisk.img".to_string(),
             partitions: vec![partition1, partition2],
-            is_removable: false,
-            model: None,
-            serial: None,
             size: Size::new(42),
-            uuid: GptUUID::NotAvailable,
+            ..Device::fixture("gptdisk.img")
         };
-        device = enrich_with_gpt_uuid(device);
+        let dev_dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/resources", "/test"));
+        // the image has 512 byte sectors, there is no header at 4096 bytes
+        let with_4k_sectors = enrich_with_gpt_uuid(device.clone(), dev_dir, 4096);
+        assert_eq!(GptUUID::NotAvailable, with_4k_sectors.uuid);
+        // the gpt crate can't read a table with 2048 byte sectors
+        let with_2k_sectors = enrich_with_gpt_uuid(device.clone(), dev_dir, 2048);
+        assert_eq!(GptUUID::NotAvailable, with_2k_sectors.uuid);
+        assert_eq!(
+            (GptUUID::NotAvailable, BTreeMap::new()),
+            read_image_gpt(&dev_dir.join("gptdisk.img"), 2048)
+        );
+        device = enrich_with_gpt_uuid(device, dev_dir, 512);
 
         match device.uuid {
             GptUUID::UUID(uuid) => assert_eq!("f0ce7b2c-74af-47e4-8141-b2fe24ac20cc", uuid),
             _ => panic!("No UUID"),
         }
-        match &device
+        let gpt1 = device
             .partitions
             .iter()
             .find(|&partition| partition.number == 1)
             .unwrap()
-            .part_uuid
-        {
-            GptUUID::UUID(uuid) => assert_eq!("3cdd6997-9b47-46f1-a160-49546976c24e", uuid),
-            _ => panic!("Partition 1 - no UUID"),
-        }
-        match &device
+            .gpt
+            .as_ref()
+            .expect("Partition 1 - no GPT entry");
+        assert_eq!("3cdd6997-9b47-46f1-a160-49546976c24e", gpt1.part_uuid);
+        assert_eq!("0fc63daf-8483-4772-8e79-3d69d8477de4", gpt1.type_guid);
+        assert_eq!(GptPartitionType::LinuxFilesystem, gpt1.partition_type);
+        assert_eq!("test1", gpt1.name);
+        assert_eq!(34, gpt1.first_lba);
+        assert_eq!(1057, gpt1.last_lba);
+        assert_eq!(GptAttributes::default(), gpt1.attributes);
+
+        let gpt2 = device
             .partitions
             .iter()
             .find(|&partition| partition.number == 2)
             .unwrap()
-            .part_uuid
-        {
-            GptUUID::UUID(uuid) => assert_eq!("4d3adf65-ff1b-473c-8f5e-b6c8d228b8d4", uuid),
-            _ => panic!("Partition 2 - no UUID"),
-        }
+            .gpt
+            .as_ref()
+            .expect("Partition 2 - no GPT entry");
+        assert_eq!("4d3adf65-ff1b-473c-8f5e-b6c8d228b8d4", gpt2.part_uuid);
+        assert_eq!("test2", gpt2.name);
+        assert_eq!(1058, gpt2.first_lba);
+        assert_eq!(2014, gpt2.last_lba);
     }
 }
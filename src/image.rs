@@ -0,0 +1,437 @@
+//!
+//! Inspection of disk image files (`.img` files, raw dumps of a device).
+//!
+//! The partition table, the partitions with their offsets, sizes and types and
+//! the filesystems are read from the file and returned as a [`Device`], the
+//! same model [`crate::get_devices`] uses for block devices. Fields that only
+//! exist for block devices (device number, mounts, udev properties, model and
+//! serial) are left empty.
+//!
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{
+    error::DrivesError, fs_wrap::path_to_string, gpt, mbr, probe, Device, DeviceKind, GptUUID,
+    MbrPartition, Partition, PartitionTableType, Size, Transport,
+};
+
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+
+// restricts a reader to the byte range of a partition, so its filesystem can
+// be probed like on the partition device node
+struct PartitionReader<'a, R> {
+    inner: &'a mut R,
+    offset: u64,
+    len: u64,
+    position: u64,
+}
+
+impl<R: Read + Seek> Read for PartitionReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.position);
+        let max = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        if max == 0 {
+            return Ok(0);
+        }
+        self.inner
+            .seek(SeekFrom::Start(self.offset + self.position))?;
+        let read = self.inner.read(&mut buf[..max])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for PartitionReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+// images of 4Kn disks have the GPT header at 4096 instead of 512, everything
+// else is expected to use 512 byte sectors
+fn detect_sector_size<R: Read + Seek>(reader: &mut R) -> u64 {
+    for sector_size in [512, 4096] {
+        let mut signature = [0u8; 8];
+        let found = reader
+            .seek(SeekFrom::Start(sector_size))
+            .and_then(|_| reader.read_exact(&mut signature))
+            .is_ok();
+        if found && signature == GPT_SIGNATURE {
+            return sector_size;
+        }
+    }
+    512
+}
+
+// like the kernel names the partitions of loop devices, a `p` separates the
+// number from a name that ends with a digit
+fn partition_name(device_name: &str, number: u32) -> String {
+    if device_name.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{}p{}", device_name, number)
+    } else {
+        format!("{}{}", device_name, number)
+    }
+}
+
+// start and sectors are given in sectors of the partition table, the model
+// uses 512 byte blocks like sysfs does
+fn new_partition(
+    device_name: &str,
+    number: u32,
+    start: u64,
+    sectors: u64,
+    sector_size: u64,
+) -> Partition {
+    let blocks_per_sector = sector_size / 512;
+    Partition {
+        name: partition_name(device_name, number),
+        dev: None,
+        size: Size::new(sectors.saturating_mul(blocks_per_sector)),
+        start: Some(start.saturating_mul(blocks_per_sector)),
+        number,
+        mountpoints: vec![],
+        holders: vec![],
+        gpt: None,
+        mbr: None,
+        udev: None,
+        filesystem: None,
+    }
+}
+
+// probing is best effort, a partition beyond the end of a truncated image
+// just has no filesystem info
+fn probe_range<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    len: u64,
+) -> Option<probe::FilesystemInfo> {
+    let mut partition_reader = PartitionReader {
+        inner: reader,
+        offset,
+        len,
+        position: 0,
+    };
+    probe::probe(&mut partition_reader).ok().flatten()
+}
+
+/// Reads the partition table (GPT needs the feature "gpt" to be enabled, MBR
+/// including logical partitions), the partitions and their filesystems of a
+/// disk image file
+///
+/// The name of the returned device is the file name, partitions are named like
+/// the kernel names the partitions of a loop device with the image attached.
+pub fn inspect(path: &Path) -> Result<Device, DrivesError> {
+    let access_error = || DrivesError::FileAccessError {
+        filename: path_to_string(path),
+    };
+    let mut file = File::open(path).map_err(|_err| access_error())?;
+    let len = file.metadata().map_err(|_err| access_error())?.len();
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path_to_string(path));
+
+    let sector_size = detect_sector_size(&mut file);
+    let mbr = mbr::read_mbr(&mut file, sector_size)?;
+    let (uuid, gpt_entries) = gpt::read_image_gpt(path, sector_size);
+    // the protective entry in front of a GPT is no partition of its own
+    let mbr_partitions: Vec<&MbrPartition> = mbr
+        .iter()
+        .flat_map(|mbr| mbr.partitions.iter())
+        .filter(|partition| partition.partition_type != mbr::GPT_PROTECTIVE_TYPE)
+        .collect();
+    let find_mbr_partition = |number: u32| {
+        mbr_partitions
+            .iter()
+            .find(|partition| partition.number == number)
+            .map(|&partition| partition.clone())
+    };
+
+    let (partition_table_type, mut partitions): (_, Vec<Partition>) = if let GptUUID::UUID(_) = uuid
+    {
+        let partitions = gpt_entries
+            .into_iter()
+            .map(|(number, entry)| {
+                let sectors = (entry.last_lba + 1).saturating_sub(entry.first_lba);
+                let mut partition =
+                    new_partition(&name, number, entry.first_lba, sectors, sector_size);
+                partition.gpt = Some(entry);
+                partition.mbr = find_mbr_partition(number);
+                partition
+            })
+            .collect();
+        (PartitionTableType::Gpt, partitions)
+    } else {
+        let partitions = mbr_partitions
+            .iter()
+            .map(|&entry| {
+                let mut partition = new_partition(
+                    &name,
+                    entry.number,
+                    entry.start_lba,
+                    entry.sectors,
+                    sector_size,
+                );
+                partition.mbr = Some(entry.clone());
+                partition
+            })
+            .collect();
+        let partition_table_type = match &mbr {
+            Some(mbr) if mbr.is_protective || mbr.is_hybrid => PartitionTableType::Gpt,
+            Some(_) => PartitionTableType::Dos,
+            None => PartitionTableType::None,
+        };
+        (partition_table_type, partitions)
+    };
+
+    for partition in partitions.iter_mut() {
+        // an extended partition only holds the chain of logical partitions
+        if partition.mbr.as_ref().is_some_and(|mbr| mbr.is_extended()) {
+            continue;
+        }
+        let offset = partition.start.unwrap_or(0).saturating_mul(512);
+        let len = partition.size.get_raw_size().saturating_mul(512);
+        partition.filesystem = probe_range(&mut file, offset, len);
+    }
+
+    // a filesystem directly on the image, e.g. a superfloppy or an ISO file
+    let filesystem = if partitions.is_empty() {
+        probe_range(&mut file, 0, len)
+    } else {
+        None
+    };
+
+    Ok(Device {
+        name,
+        dev: None,
+        partitions,
+        holders: vec![],
+        slaves: vec![],
+        is_removable: false,
+        kind: DeviceKind::Disk,
+        transport: Transport::Unknown,
+        is_hotpluggable: false,
+        usb: None,
+        mmc: None,
+        nvme: None,
+        dm: None,
+        md: None,
+        loop_info: None,
+        queue: None,
+        model: None,
+        serial: None,
+        wwid: None,
+        size: Size::new(len / 512),
+        mountpoints: vec![],
+        uuid,
+        partition_table_type: Some(partition_table_type),
+        mbr,
+        udev: None,
+        filesystem,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Cursor, Write};
+
+    use tempfile::NamedTempFile;
+
+    use crate::FilesystemType;
+
+    fn mbr_entry(partition_type: u8, start: u32, sectors: u32) -> [u8; 16] {
+        let mut entry = [0u8; 16];
+        entry[4] = partition_type;
+        entry[8..12].copy_from_slice(&start.to_le_bytes());
+        entry[12..16].copy_from_slice(&sectors.to_le_bytes());
+        entry
+    }
+
+    fn write_at(image: &mut [u8], offset: usize, bytes: &[u8]) {
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    // an ext4 superblock with a label and a swap signature (4 KiB pages)
+    fn ext4_superblock(image: &mut [u8], offset: usize, label: &str) {
+        write_at(image, offset + 0x438, &0xef53u16.to_le_bytes());
+        write_at(image, offset + 0x460, &0x0040u32.to_le_bytes());
+        write_at(image, offset + 0x478, label.as_bytes());
+    }
+
+    fn swap_signature(image: &mut [u8], offset: usize) {
+        write_at(image, offset + 0x1000 - 10, b"SWAPSPACE2");
+    }
+
+    fn write_image(image: &[u8]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(image).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_partition_reader() {
+        let mut cursor = Cursor::new((0u8..100).collect::<Vec<u8>>());
+        let mut reader = PartitionReader {
+            inner: &mut cursor,
+            offset: 10,
+            len: 20,
+            position: 0,
+        };
+        let mut content = vec![];
+        reader.read_to_end(&mut content).unwrap();
+        assert_eq!((10u8..30).collect::<Vec<u8>>(), content);
+
+        assert_eq!(15, reader.seek(SeekFrom::End(-5)).unwrap());
+        let mut buffer = [0u8; 10];
+        assert_eq!(5, reader.read(&mut buffer).unwrap());
+        assert_eq!([25, 26, 27, 28, 29], buffer[..5]);
+        assert!(reader.seek(SeekFrom::Current(-21)).is_err());
+    }
+
+    #[test]
+    fn test_partition_name() {
+        assert_eq!("disk.img1", partition_name("disk.img", 1));
+        assert_eq!("raspios-2024p2", partition_name("raspios-2024", 2));
+    }
+
+    #[test]
+    fn test_inspect_mbr_image() {
+        // 2.5 MiB: an ext4 partition at 1 MiB and a swap partition at 2 MiB
+        let mut image = vec![0u8; 5120 * 512];
+        write_at(&mut image, 440, &0x1234_5678u32.to_le_bytes());
+        write_at(&mut image, 446, &mbr_entry(0x83, 2048, 2048));
+        write_at(&mut image, 462, &mbr_entry(0x82, 4096, 1024));
+        write_at(&mut image, 510, &[0x55, 0xaa]);
+        ext4_superblock(&mut image, 2048 * 512, "rootfs");
+        swap_signature(&mut image, 4096 * 512);
+        let file = write_image(&image);
+
+        let device = inspect(file.path()).unwrap();
+        let name = file.path().file_name().unwrap().to_str().unwrap();
+        assert_eq!(name, device.name);
+        assert_eq!(5120, device.size.get_raw_size());
+        assert_eq!(Some(PartitionTableType::Dos), device.partition_table_type);
+        assert_eq!("12345678", device.mbr.as_ref().unwrap().pt_uuid);
+        assert!(device.filesystem.is_none());
+        assert_eq!(2, device.partitions.len());
+
+        let root = &device.partitions[0];
+        assert_eq!(partition_name(name, 1), root.name);
+        assert_eq!(Some(2048), root.start);
+        assert_eq!(2048, root.size.get_raw_size());
+        assert_eq!(0x83, root.mbr.as_ref().unwrap().partition_type);
+        let filesystem = root.filesystem.as_ref().unwrap();
+        assert_eq!(FilesystemType::Ext4, filesystem.fs_type);
+        assert_eq!(Some("rootfs".to_string()), filesystem.label);
+
+        let swap = &device.partitions[1];
+        assert_eq!(2, swap.number);
+        assert_eq!(Some(4096), swap.start);
+        assert_eq!(1024, swap.size.get_raw_size());
+        assert_eq!(
+            FilesystemType::Swap,
+            swap.filesystem.as_ref().unwrap().fs_type
+        );
+    }
+
+    #[test]
+    fn test_inspect_unpartitioned_image() {
+        let mut image = vec![0u8; 64 * 1024];
+        swap_signature(&mut image, 0);
+        let file = write_image(&image);
+
+        let device = inspect(file.path()).unwrap();
+        assert_eq!(Some(PartitionTableType::None), device.partition_table_type);
+        assert!(device.partitions.is_empty());
+        assert!(device.mbr.is_none());
+        assert_eq!(
+            FilesystemType::Swap,
+            device.filesystem.as_ref().unwrap().fs_type
+        );
+    }
+
+    #[test]
+    fn test_inspect_superfloppy_image() {
+        // a 16 MiB FAT16 filesystem without partition table like mkfs.fat
+        // writes it, the boot sector has zeros in the area of the MBR entries
+        let mut image = vec![0u8; 32768 * 512];
+        write_at(&mut image, 0, b"\xeb\x3c\x90mkfs.fat");
+        write_at(&mut image, 0x0b, &512u16.to_le_bytes());
+        write_at(&mut image, 0x0d, &[4]);
+        write_at(&mut image, 0x0e, &4u16.to_le_bytes());
+        write_at(&mut image, 0x10, &[2]);
+        write_at(&mut image, 0x11, &512u16.to_le_bytes());
+        write_at(&mut image, 0x13, &32768u16.to_le_bytes());
+        write_at(&mut image, 0x16, &32u16.to_le_bytes());
+        write_at(&mut image, 0x26, &[0x29]);
+        write_at(&mut image, 0x27, &0x4a3b_1c2du32.to_le_bytes());
+        write_at(&mut image, 0x2b, b"FLOPPY     FAT16   ");
+        write_at(&mut image, 510, &[0x55, 0xaa]);
+        let file = write_image(&image);
+
+        let device = inspect(file.path()).unwrap();
+        assert_eq!(Some(PartitionTableType::None), device.partition_table_type);
+        assert!(device.partitions.is_empty());
+        assert!(device.mbr.is_none());
+        let filesystem = device.filesystem.as_ref().unwrap();
+        assert_eq!(FilesystemType::Fat16, filesystem.fs_type);
+        assert_eq!(Some("FLOPPY".to_string()), filesystem.label);
+    }
+
+    #[test]
+    fn test_inspect_gpt_image() {
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/resources/test/gptdisk.img"
+        ));
+        let device = inspect(path).unwrap();
+        assert_eq!("gptdisk.img", device.name);
+        assert_eq!(Some(PartitionTableType::Gpt), device.partition_table_type);
+        assert!(device.mbr.as_ref().unwrap().is_protective);
+
+        #[cfg(not(feature = "gpt"))]
+        {
+            assert!(matches!(device.uuid, GptUUID::FeatureNotEnabled));
+            assert!(device.partitions.is_empty());
+        }
+        #[cfg(feature = "gpt")]
+        {
+            match &device.uuid {
+                GptUUID::UUID(uuid) => assert_eq!("f0ce7b2c-74af-47e4-8141-b2fe24ac20cc", uuid),
+                _ => panic!("No UUID"),
+            }
+            assert_eq!(2, device.partitions.len());
+            let partition = &device.partitions[0];
+            assert_eq!("gptdisk.img1", partition.name);
+            assert_eq!(Some(34), partition.start);
+            assert_eq!(1024, partition.size.get_raw_size());
+            assert_eq!("test1", partition.gpt.as_ref().unwrap().name);
+            assert!(partition.mbr.is_none());
+            assert_eq!(Some(1058), device.partitions[1].start);
+        }
+    }
+
+    #[test]
+    fn test_inspect_missing_image() {
+        let result = inspect(Path::new("/does/not/exist.img"));
+        assert!(matches!(result, Err(DrivesError::FileAccessError { .. })));
+    }
+}
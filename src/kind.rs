@@ -0,0 +1,291 @@
+use std::{fs, path::Path};
+
+use crate::fs_wrap;
+
+/// What kind of block device it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceKind {
+    /// a disk like device (hard disk, SSD, flash drive, sd card, virtual disk of a VM)
+    Disk,
+    /// an optical drive (CD, DVD, Blu-ray)
+    Optical,
+    /// a boot area of an eMMC (`mmcblk0boot0`, `mmcblk0boot1`)
+    MmcBoot,
+    /// the replay protected memory block of an eMMC (`mmcblk0rpmb`)
+    MmcRpmb,
+    /// a loop device backed by a file
+    Loop,
+    /// a device mapper device (LVM, LUKS, multipath, ...)
+    DeviceMapper,
+    /// a software RAID (md) device
+    Md,
+    /// a ram disk (brd)
+    Ram,
+    /// a compressed ram disk, usually used as swap
+    Zram,
+    /// any other device without backing hardware (e.g. nbd)
+    Virtual,
+}
+
+impl DeviceKind {
+    /// a short name like used by lsblk (`disk`, `rom`, `loop`, `dm`, `md`, ...)
+    pub fn name(&self) -> &str {
+        match *self {
+            DeviceKind::Disk => "disk",
+            DeviceKind::Optical => "rom",
+            DeviceKind::MmcBoot => "boot",
+            DeviceKind::MmcRpmb => "rpmb",
+            DeviceKind::Loop => "loop",
+            DeviceKind::DeviceMapper => "dm",
+            DeviceKind::Md => "md",
+            DeviceKind::Ram => "ram",
+            DeviceKind::Zram => "zram",
+            DeviceKind::Virtual => "virtual",
+        }
+    }
+}
+
+/// The bus a block device is attached by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transport {
+    /// NVM Express
+    Nvme,
+    /// (S)ATA via libata
+    Sata,
+    /// SCSI or SAS without ATA or USB in between
+    Scsi,
+    /// USB mass storage (also UAS)
+    Usb,
+    /// MMC/SD host controller (sd cards, eMMC, SDIO)
+    Mmc,
+    /// paravirtualized disk of a virtual machine
+    Virtio,
+    /// no hardware, the device lives below `/sys/devices/virtual`
+    Virtual,
+    /// couldn't be determined, e.g. for an image file
+    Unknown,
+}
+
+impl Transport {
+    /// a short name like used by lsblk (`nvme`, `sata`, `usb`, ...)
+    pub fn name(&self) -> &str {
+        match *self {
+            Transport::Nvme => "nvme",
+            Transport::Sata => "sata",
+            Transport::Scsi => "scsi",
+            Transport::Usb => "usb",
+            Transport::Mmc => "mmc",
+            Transport::Virtio => "virtio",
+            Transport::Virtual => "virtual",
+            Transport::Unknown => "unknown",
+        }
+    }
+}
+
+// e.g. usb1, ata3 or virtio2, but not usb1-port or 1-1:1.0
+fn is_numbered(component: &str, prefix: &str) -> bool {
+    component
+        .strip_prefix(prefix)
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Determines the transport from the target of the `/sys/block/<name>` symlink
+/// (e.g. `../devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0/block/sda`)
+///
+/// The parent chain is walked from the root towards the device, the outermost
+/// bus wins: a disk behind an USB bridge also has a SCSI host.
+pub(crate) fn transport_from_link(target: &Path) -> Transport {
+    let components: Vec<&str> = target
+        .components()
+        .filter_map(|component| component.as_os_str().to_str())
+        .collect();
+    // the head of a multipath namespace and the controllers of NVMe over
+    // Fabrics live below /sys/devices/virtual
+    if components.contains(&"nvme-subsystem") || components.contains(&"nvme-fabrics") {
+        return Transport::Nvme;
+    } else if components.contains(&"virtual") {
+        return Transport::Virtual;
+    }
+    for component in components {
+        if is_numbered(component, "usb") {
+            return Transport::Usb;
+        } else if component == "mmc_host" {
+            return Transport::Mmc;
+        } else if component == "nvme" {
+            return Transport::Nvme;
+        } else if is_numbered(component, "ata") {
+            return Transport::Sata;
+        } else if is_numbered(component, "virtio") {
+            return Transport::Virtio;
+        } else if is_numbered(component, "host") {
+            return Transport::Scsi;
+        }
+    }
+    Transport::Unknown
+}
+
+// the hardware partitions of an eMMC are named after the user area, e.g.
+// `boot0` for mmcblk0boot0
+fn mmc_area(name: &str) -> Option<&str> {
+    let area = name
+        .strip_prefix("mmcblk")?
+        .trim_start_matches(|c: char| c.is_ascii_digit());
+    (!area.is_empty()).then_some(area)
+}
+
+/// Determines the kind of the device from the driver specific sysfs directories
+/// (`loop`, `dm`, `md`) and the names used by the kernel
+pub(crate) fn kind(name: &str, device_dir: &Path, transport: Transport) -> DeviceKind {
+    if let Some(area) = mmc_area(name) {
+        if area.starts_with("boot") {
+            return DeviceKind::MmcBoot;
+        } else if area == "rpmb" {
+            return DeviceKind::MmcRpmb;
+        }
+    }
+    if device_dir.join("loop").is_dir() || name.starts_with("loop") {
+        DeviceKind::Loop
+    } else if device_dir.join("dm").is_dir() || name.starts_with("dm-") {
+        DeviceKind::DeviceMapper
+    } else if device_dir.join("md").is_dir() || name.starts_with("md") {
+        DeviceKind::Md
+    } else if name.starts_with("zram") {
+        DeviceKind::Zram
+    } else if name.starts_with("ram") {
+        DeviceKind::Ram
+    } else if name.starts_with("sr") {
+        DeviceKind::Optical
+    } else if transport == Transport::Virtual {
+        DeviceKind::Virtual
+    } else {
+        DeviceKind::Disk
+    }
+}
+
+/// Whether the device can be plugged in and out while the system is running:
+/// everything behind USB and cards in an SD/SDIO slot, but not a soldered eMMC
+/// (card type `MMC`). The `removable` attribute is not used, it is 0 for many
+/// USB SSDs and for some card readers.
+pub(crate) fn is_hotpluggable(device_dir: &Path, transport: Transport) -> bool {
+    match transport {
+        Transport::Usb => true,
+        Transport::Mmc => fs_wrap::read_file_to_string(&device_dir.join("device").join("type"))
+            .map_or(true, |card_type| card_type != "MMC"),
+        _ => false,
+    }
+}
+
+/// the transport for a `/sys/block/<name>` entry, a plain directory (instead
+/// of a symlink) gives no hint about the parents
+pub(crate) fn transport(entry_path: &Path) -> Transport {
+    match fs::read_link(entry_path) {
+        Ok(target) => transport_from_link(&target),
+        Err(_) => Transport::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_transport_from_link() {
+        let cases = [
+            (
+                "../devices/pci0000:00/0000:00:1d.0/0000:3d:00.0/nvme/nvme0/nvme0n1",
+                Transport::Nvme,
+            ),
+            (
+                "../devices/pci0000:00/0000:00:17.0/ata1/host0/target0:0:0/0:0:0:0/block/sda",
+                Transport::Sata,
+            ),
+            (
+                "../devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0/block/sdb",
+                Transport::Usb,
+            ),
+            (
+                "../devices/pci0000:00/0000:00:14.0/usb1/1-3/1-3:1.0/host4/target4:0:0/4:0:0:0/block/sdc",
+                Transport::Usb,
+            ),
+            (
+                "../devices/platform/soc/fe340000.mmc/mmc_host/mmc0/mmc0:aaaa/block/mmcblk0",
+                Transport::Mmc,
+            ),
+            (
+                "../devices/pci0000:00/0000:00:04.0/virtio1/block/vda",
+                Transport::Virtio,
+            ),
+            (
+                "../devices/pci0000:00/0000:00:03.0/0000:02:00.0/host2/port-2:0/end_device-2:0/target2:0:0/2:0:0:0/block/sdd",
+                Transport::Scsi,
+            ),
+            (
+                "../devices/virtual/nvme-subsystem/nvme-subsys0/nvme0n1",
+                Transport::Nvme,
+            ),
+            (
+                "../devices/virtual/nvme-fabrics/ctl/nvme1/nvme1n1",
+                Transport::Nvme,
+            ),
+            ("../devices/virtual/block/loop0", Transport::Virtual),
+            ("../devices/platform/something/block/xyz0", Transport::Unknown),
+        ];
+        for (target, transport) in cases {
+            assert_eq!(
+                transport,
+                transport_from_link(Path::new(target)),
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_kind() {
+        let temp_dir = tempdir().unwrap();
+        let dm_dir = temp_dir.path().join("dm-0");
+        fs::create_dir_all(dm_dir.join("dm")).unwrap();
+        assert_eq!(
+            DeviceKind::DeviceMapper,
+            kind("dm-0", &dm_dir, Transport::Virtual)
+        );
+
+        let unknown_dir = temp_dir.path().join("unknown");
+        for (name, transport, expected) in [
+            ("loop3", Transport::Virtual, DeviceKind::Loop),
+            ("md127", Transport::Virtual, DeviceKind::Md),
+            ("zram0", Transport::Virtual, DeviceKind::Zram),
+            ("ram0", Transport::Virtual, DeviceKind::Ram),
+            ("nbd0", Transport::Virtual, DeviceKind::Virtual),
+            ("sr0", Transport::Sata, DeviceKind::Optical),
+            ("sda", Transport::Usb, DeviceKind::Disk),
+            ("nvme0n1", Transport::Nvme, DeviceKind::Disk),
+            ("mmcblk0", Transport::Mmc, DeviceKind::Disk),
+            ("mmcblk0boot1", Transport::Mmc, DeviceKind::MmcBoot),
+            ("mmcblk1rpmb", Transport::Mmc, DeviceKind::MmcRpmb),
+        ] {
+            assert_eq!(expected, kind(name, &unknown_dir, transport), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_is_hotpluggable() {
+        let temp_dir = tempdir().unwrap();
+        let sd_dir = temp_dir.path().join("mmcblk0");
+        fs::create_dir_all(sd_dir.join("device")).unwrap();
+        fs::write(sd_dir.join("device").join("type"), "SD\n").unwrap();
+        let emmc_dir = temp_dir.path().join("mmcblk1");
+        fs::create_dir_all(emmc_dir.join("device")).unwrap();
+        fs::write(emmc_dir.join("device").join("type"), "MMC\n").unwrap();
+
+        assert!(is_hotpluggable(&sd_dir, Transport::Mmc));
+        assert!(!is_hotpluggable(&emmc_dir, Transport::Mmc));
+        assert!(is_hotpluggable(temp_dir.path(), Transport::Usb));
+        assert!(!is_hotpluggable(temp_dir.path(), Transport::Sata));
+        assert!(!is_hotpluggable(temp_dir.path(), Transport::Nvme));
+    }
+}
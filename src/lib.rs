@@ -4,66 +4,407 @@
 //!
 //! Uses `/sys/block` to retreive information.
 //!
+//! For the default locations [`get_devices`] is the simplest entry point. When the
+//! kernel filesystems are mounted somewhere else (e.g. inside a container where the
+//! host trees are bind-mounted below `/host`) a [`Drives`] instance can be configured
+//! using the [`DrivesBuilder`]:
+//!
+//! ```no_run
+//! let drives = drives::DrivesBuilder::new()
+//!     .sysfs_root("/host/sys")
+//!     .procfs_root("/host/proc")
+//!     .dev_root("/host/dev")
+//!     .include_virtual(false)
+//!     .build();
+//! let devices = drives.get_devices().unwrap();
+//! ```
+//!
 
 use fs_wrap::build_path;
-use mounts::Mounts;
+use mounts::{Mounts, ResolvedMount};
 
+mod device_number;
+mod diff;
+mod dm;
 mod error;
+mod filter;
 mod fs_wrap;
 mod gpt;
+pub mod image;
+mod kind;
+mod loop_device;
+mod mbr;
+mod md;
+mod mmc;
+mod monitor;
+mod mount_options;
+mod mount_watcher;
 mod mounts;
+mod nvme;
+pub mod probe;
+mod queue;
 mod size;
+mod topology;
+mod udev;
+mod usage;
+mod usb;
 
+pub use device_number::DeviceNumber;
+pub use diff::{diff, Change, UuidKind};
+pub use dm::{DmInfo, DmTarget};
 pub use error::DrivesError;
-pub use mounts::Mount;
+pub use filter::DeviceFilter;
+pub use gpt::{GptAttributes, GptInfo, GptPartitionType, GptUUID};
+pub use kind::{DeviceKind, Transport};
+pub use loop_device::{LoopInfo, LoopSource};
+pub use mbr::{Chs, MbrInfo, MbrPartition, PartitionTableType};
+pub use md::{MdInfo, MdMember, MdStat, MdStatMember, MdSyncProgress};
+pub use mmc::{MmcCardType, MmcInfo};
+pub use monitor::{DriveEvent, DriveMonitor, MonitorBackend, NetlinkBackend, PollingBackend};
+pub use mount_options::MountOptions;
+pub use mount_watcher::{diff_mounts, MountChange, MountWatcher};
+pub use mounts::{Mount, MountInfo};
+pub use nvme::{NvmeInfo, NvmePath};
+pub use probe::{FilesystemInfo, FilesystemType};
+pub use queue::{QueueInfo, WriteCache, ZonedModel};
 pub use size::{Size, Unit};
-pub use gpt::GptUUID;
+pub use topology::StorageTopology;
+pub use udev::UdevInfo;
+pub use usage::FilesystemUsage;
+pub use usb::UsbInfo;
+
+use std::{fs::DirEntry, path::PathBuf};
 
-use std::fs::DirEntry;
+const DEFAULT_SYSFS_ROOT: &str = "/sys";
+const DEFAULT_PROCFS_ROOT: &str = "/proc";
+const DEFAULT_DEV_ROOT: &str = "/dev";
+const DEFAULT_UDEV_DATA_DIR: &str = "/run/udev/data";
 
 /// A block device
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     /// The name of the block device
     pub name: String,
+    /// major:minor of the block device
+    pub dev: Option<DeviceNumber>,
     /// list of partitions
     pub partitions: Vec<Partition>,
+    /// names of the devices built on this one, e.g. a device mapper or md device
+    /// (`holders` in sysfs)
+    pub holders: Vec<String>,
+    /// names of the devices this one is built on, e.g. the members of an md
+    /// raid or the encrypted partition of a LUKS device (`slaves` in sysfs)
+    pub slaves: Vec<String>,
     /// is it a fixed device or a removable one like a flash drive or sd card
     pub is_removable: bool,
+    /// what kind of device it is (disk, loop, device mapper, ...)
+    pub kind: DeviceKind,
+    /// the bus the device is attached by
+    pub transport: Transport,
+    /// whether the device can be plugged in and out while running, true for
+    /// everything behind USB or an SD/SDIO slot even if `is_removable` is false
+    pub is_hotpluggable: bool,
+    /// vendor, product, speed and driver of the USB device the drive is
+    /// attached by, if the transport is USB
+    pub usb: Option<UsbInfo>,
+    /// the decoded CID of an SD card or eMMC
+    pub mmc: Option<MmcInfo>,
+    /// controller and namespace details of an NVMe namespace, with the paths
+    /// of a multipath namespace
+    pub nvme: Option<NvmeInfo>,
+    /// mapper name, suspended state and the decoded owner (LVM, LUKS, multipath)
+    /// of a device mapper device
+    pub dm: Option<DmInfo>,
+    /// level, state, sync progress and members of a software RAID (md) array,
+    /// cross-checked with `/proc/mdstat`
+    pub md: Option<MdInfo>,
+    /// backing file and attributes of an attached loop device
+    pub loop_info: Option<LoopInfo>,
+    /// sector sizes, I/O hints, rotational, discard and zone support and the
+    /// I/O scheduler
+    pub queue: Option<QueueInfo>,
     /// the model string - most common a combination of vendor name and model identifier
     /// will be None for virtual devices
     pub model: Option<String>,
     /// the hardware serial string
     pub serial: Option<String>,
+    /// the world wide identifier, unique for the device (e.g. `eui.`, `naa.` or `t10.` prefixed)
+    pub wwid: Option<String>,
     /// size of the device
     pub size: Size,
+    /// mounts of a filesystem directly on the device (without a partition table),
+    /// e.g. a device mapper volume or a superfloppy formatted flash drive
+    pub mountpoints: Vec<Mount>,
     /// the GUID from GPT (needs feature "gpt" to be enabled)
     pub uuid: GptUUID,
+    /// the kind of partition table, None if it couldn't be determined
+    pub partition_table_type: Option<PartitionTableType>,
+    /// the MBR partition table (also present as protective MBR on GPT disks)
+    pub mbr: Option<MbrInfo>,
+    /// properties from the udev database, if available
+    pub udev: Option<UdevInfo>,
+    /// the filesystem directly on the device (without a partition table), if
+    /// probing was enabled and one was detected
+    pub filesystem: Option<FilesystemInfo>,
 }
 
 /// partition of a device
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Partition {
     /// the name of the partitions
     pub name: String,
+    /// major:minor of the partition
+    pub dev: Option<DeviceNumber>,
     /// size of the partition on 512 byte blocks
     pub size: Size,
+    /// the first 512 byte block of the partition, relative to the start of the device
+    pub start: Option<u64>,
     /// the partition number
     pub number: u32,
-    /// the mountpoint if mounted
-    pub mountpoint: Option<Mount>,
-    /// the PartUUID from GPT (needs feature "gpt" to be enabled)
-    pub part_uuid: GptUUID,
+    /// all mounts of the partition, more than one for bind mounts or when
+    /// mounted at several locations, empty if not mounted
+    pub mountpoints: Vec<Mount>,
+    /// names of the devices built on the partition, e.g. a LUKS or LVM device
+    /// mapper device or an md raid (`holders` in sysfs)
+    pub holders: Vec<String>,
+    /// the partition entry from GPT with PartUUID, type, name, LBA range and
+    /// attributes (needs feature "gpt" to be enabled)
+    pub gpt: Option<GptInfo>,
+    /// the entry from the MBR partition table
+    pub mbr: Option<MbrPartition>,
+    /// properties from the udev database like filesystem type, label and UUID, if available
+    pub udev: Option<UdevInfo>,
+    /// the filesystem read from the superblock, if probing was enabled and one was detected
+    pub filesystem: Option<FilesystemInfo>,
+}
+
+// minimal devices and partitions for test fixtures, the interesting fields are
+// set with struct update syntax
+#[cfg(test)]
+impl Device {
+    pub(crate) fn fixture(name: &str) -> Device {
+        Device {
+            name: name.to_string(),
+            dev: None,
+            partitions: vec![],
+            holders: vec![],
+            slaves: vec![],
+            is_removable: false,
+            kind: DeviceKind::Disk,
+            transport: Transport::Unknown,
+            is_hotpluggable: false,
+            usb: None,
+            mmc: None,
+            nvme: None,
+            dm: None,
+            md: None,
+            loop_info: None,
+            queue: None,
+            model: None,
+            serial: None,
+            wwid: None,
+            size: Size::new(0),
+            mountpoints: vec![],
+            uuid: GptUUID::NotAvailable,
+            partition_table_type: None,
+            mbr: None,
+            udev: None,
+            filesystem: None,
+        }
+    }
 }
 
-struct Drives {
+#[cfg(test)]
+impl Partition {
+    pub(crate) fn fixture(name: &str, number: u32) -> Partition {
+        Partition {
+            name: name.to_string(),
+            dev: None,
+            size: Size::new(0),
+            start: None,
+            number,
+            mountpoints: vec![],
+            holders: vec![],
+            gpt: None,
+            mbr: None,
+            udev: None,
+            filesystem: None,
+        }
+    }
+}
+
+/// Builder for a configured [`Drives`] instance
+///
+/// All roots default to the locations used on a regular linux system
+/// (`/sys`, `/proc` and `/dev`).
+#[derive(Debug, Clone)]
+pub struct DrivesBuilder {
+    sysfs_root: PathBuf,
+    procfs_root: PathBuf,
+    dev_root: PathBuf,
+    udev_data_dir: PathBuf,
+    include_virtual: bool,
+    include_unattached_loops: bool,
+    filter: DeviceFilter,
+    read_gpt: bool,
+    read_mbr: bool,
+    read_udev: bool,
+    probe_filesystems: bool,
+}
+
+impl Default for DrivesBuilder {
+    fn default() -> Self {
+        DrivesBuilder {
+            sysfs_root: PathBuf::from(DEFAULT_SYSFS_ROOT),
+            procfs_root: PathBuf::from(DEFAULT_PROCFS_ROOT),
+            dev_root: PathBuf::from(DEFAULT_DEV_ROOT),
+            udev_data_dir: PathBuf::from(DEFAULT_UDEV_DATA_DIR),
+            include_virtual: true,
+            include_unattached_loops: false,
+            filter: DeviceFilter::default(),
+            read_gpt: true,
+            read_mbr: true,
+            read_udev: true,
+            probe_filesystems: false,
+        }
+    }
+}
+
+impl DrivesBuilder {
+    /// creates a builder using the default roots
+    pub fn new() -> DrivesBuilder {
+        DrivesBuilder::default()
+    }
+
+    /// sets the root of the sysfs tree (default `/sys`), block devices are
+    /// read from `<sysfs_root>/block`
+    pub fn sysfs_root(mut self, path: impl Into<PathBuf>) -> DrivesBuilder {
+        self.sysfs_root = path.into();
+        self
+    }
+
+    /// sets the root of the procfs tree (default `/proc`), used to read the mounts
+    /// and the md array status
+    pub fn procfs_root(mut self, path: impl Into<PathBuf>) -> DrivesBuilder {
+        self.procfs_root = path.into();
+        self
+    }
+
+    /// sets the directory holding the device nodes (default `/dev`)
+    pub fn dev_root(mut self, path: impl Into<PathBuf>) -> DrivesBuilder {
+        self.dev_root = path.into();
+        self
+    }
+
+    /// sets the directory of the udev database (default `/run/udev/data`)
+    pub fn udev_data_dir(mut self, path: impl Into<PathBuf>) -> DrivesBuilder {
+        self.udev_data_dir = path.into();
+        self
+    }
+
+    /// whether virtual devices (loop, ram, device mapper, ...) should be listed (default true)
+    pub fn include_virtual(mut self, include_virtual: bool) -> DrivesBuilder {
+        self.include_virtual = include_virtual;
+        self
+    }
+
+    /// whether loop devices without backing file (size 0) should be listed
+    /// (default false)
+    pub fn include_unattached_loops(mut self, include_unattached_loops: bool) -> DrivesBuilder {
+        self.include_unattached_loops = include_unattached_loops;
+        self
+    }
+
+    /// sets the criteria for the listed devices (default all devices), see [`DeviceFilter`]
+    pub fn filter(mut self, filter: DeviceFilter) -> DrivesBuilder {
+        self.filter = filter;
+        self
+    }
+
+    /// whether the GUID partition table should be read (default true, has no
+    /// effect when the feature "gpt" is not enabled)
+    pub fn read_gpt(mut self, read_gpt: bool) -> DrivesBuilder {
+        self.read_gpt = read_gpt;
+        self
+    }
+
+    /// whether the MBR (DOS) partition table should be read (default true, needs
+    /// read access to the device nodes which usually requires elevated privileges)
+    pub fn read_mbr(mut self, read_mbr: bool) -> DrivesBuilder {
+        self.read_mbr = read_mbr;
+        self
+    }
+
+    /// whether devices and partitions should be enriched with the properties
+    /// from the udev database (default true)
+    pub fn read_udev(mut self, read_udev: bool) -> DrivesBuilder {
+        self.read_udev = read_udev;
+        self
+    }
+
+    /// whether the superblocks of partitions (and of devices without partitions)
+    /// should be read to detect filesystems (default false, needs read access
+    /// to the device nodes which usually requires elevated privileges)
+    pub fn probe_filesystems(mut self, probe_filesystems: bool) -> DrivesBuilder {
+        self.probe_filesystems = probe_filesystems;
+        self
+    }
+
+    /// builds the configured [`Drives`] instance
+    pub fn build(self) -> Drives {
+        Drives {
+            base_path: fs_wrap::path_to_string(&self.sysfs_root.join("block")),
+            mounts: Mounts::new(&self.procfs_root),
+            mdstat_path: self.procfs_root.join("mdstat"),
+            dev_path: self.dev_root,
+            udev_data_dir: self.read_udev.then_some(self.udev_data_dir),
+            include_virtual: self.include_virtual,
+            include_unattached_loops: self.include_unattached_loops,
+            filter: self.filter,
+            read_gpt: self.read_gpt,
+            read_mbr: self.read_mbr,
+            probe_filesystems: self.probe_filesystems,
+        }
+    }
+}
+
+/// Reads the block devices from a (configurable) sysfs, procfs and dev tree
+///
+/// Use [`DrivesBuilder`] to create an instance with non-default settings.
+#[derive(Debug)]
+pub struct Drives {
     base_path: String,
     mounts: Mounts,
+    mdstat_path: PathBuf,
+    dev_path: PathBuf,
+    udev_data_dir: Option<PathBuf>,
+    include_virtual: bool,
+    include_unattached_loops: bool,
+    filter: DeviceFilter,
+    read_gpt: bool,
+    read_mbr: bool,
+    probe_filesystems: bool,
+}
+
+impl Default for Drives {
+    fn default() -> Self {
+        DrivesBuilder::new().build()
+    }
 }
 
 impl Drives {
-    fn find_partitions(&self, dir_entry: &DirEntry) -> Result<Vec<Partition>, DrivesError> {
-        let mount_points = self.mounts.read_mountpoints()?;
+    /// returns a builder to configure a new instance
+    pub fn builder() -> DrivesBuilder {
+        DrivesBuilder::new()
+    }
+
+    fn find_partitions(
+        &self,
+        dir_entry: &DirEntry,
+        mounts: &[ResolvedMount],
+    ) -> Result<Vec<Partition>, DrivesError> {
         let mut partitions = vec![];
         let base_dir_name = fs_wrap::name_from_direntry(dir_entry)?;
         let dir_entry_path = if let Some(dir_entry_path) = dir_entry.path().to_str() {
@@ -78,16 +419,28 @@ impl Drives {
             if let Ok(file_type) = entry.file_type() {
                 if file_type.is_dir() {
                     let dir_name = fs_wrap::name_from_direntry(&entry)?;
-                    if dir_name.starts_with(&base_dir_name) {
+                    // the boot areas of an eMMC (mmcblk0boot0) are sub-directories
+                    // too, but separate devices without a partition number
+                    if dir_name.starts_with(&base_dir_name)
+                        && entry.path().join("partition").exists()
+                    {
                         let size = fs_wrap::read_file_to_u64(&build_path(&entry, "/size")?)?;
-                        let mount = self.find_mountpoint_for_partition(&mount_points, &dir_name)?;
+                        let start = fs_wrap::read_file_to_u64(&build_path(&entry, "/start")?).ok();
+                        let dev = fs_wrap::read_dev_file(&build_path(&entry, "/dev")?);
+                        let mountpoints = self.find_mountpoints(mounts, &dir_name, dev);
                         let number = fs_wrap::read_file_to_u32(&build_path(&entry, "/partition")?)?;
                         partitions.push(Partition {
                             name: dir_name,
+                            dev,
                             size: Size::new(size),
+                            start,
                             number,
-                            mountpoint: mount,
-                            part_uuid: GptUUID::NotAvailable,
+                            mountpoints,
+                            holders: topology::read_links(&entry.path().join("holders")),
+                            gpt: None,
+                            mbr: None,
+                            udev: None,
+                            filesystem: None,
                         });
                     }
                 }
@@ -100,18 +453,44 @@ impl Drives {
         Ok(partitions)
     }
 
-    fn find_mountpoint_for_partition(
+    fn find_mountpoints(
         &self,
-        mounts: &[Mount],
-        partition_name: &str,
-    ) -> Result<Option<Mount>, DrivesError> {
-        let found_mount = mounts
+        mounts: &[ResolvedMount],
+        name: &str,
+        dev: Option<DeviceNumber>,
+    ) -> Vec<Mount> {
+        mounts
             .iter()
-            .find(|mount| mount.device.contains(partition_name));
-        if let Some(mount) = found_mount {
-            return Ok(Some(mount.clone()));
+            .filter(|mount| mount.is_mount_of(name, dev))
+            .map(|mount| mount.mount.clone())
+            .collect()
+    }
+
+    fn read_udev_info(&self, dev: Option<DeviceNumber>) -> Option<UdevInfo> {
+        udev::read_udev_info(self.udev_data_dir.as_ref()?, dev?)
+    }
+
+    // probing is best effort, a device node that can't be read has no filesystem info
+    fn probe_filesystem(&self, name: &str) -> Option<FilesystemInfo> {
+        if !self.probe_filesystems {
+            return None;
+        }
+        probe::probe_path(&self.dev_path.join(name)).ok().flatten()
+    }
+
+    // a GPT header wins over a missing protective MBR, without access to the
+    // device node the type known by udev is used
+    fn detect_partition_table_type(&self, device: &Device) -> Option<PartitionTableType> {
+        if let GptUUID::UUID(_) = device.uuid {
+            return Some(PartitionTableType::Gpt);
         }
-        Ok(None)
+        device.partition_table_type.or_else(|| {
+            match device.udev.as_ref()?.part_table_type.as_deref()? {
+                "gpt" => Some(PartitionTableType::Gpt),
+                "dos" => Some(PartitionTableType::Dos),
+                _ => None,
+            }
+        })
     }
 
     fn read_model_and_serial_if_available(
@@ -131,50 +510,157 @@ impl Drives {
         (model, serial)
     }
 
-    fn get_devices(&self) -> Result<Vec<Device>, DrivesError> {
+    // nvme namespaces have the wwid next to the size, scsi disks in their device directory
+    fn read_wwid(&self, dir_entry: &DirEntry) -> Option<String> {
+        ["wwid", "device/wwid"]
+            .into_iter()
+            .find_map(|file| fs_wrap::read_file_to_string(&dir_entry.path().join(file)).ok())
+            .filter(|wwid| !wwid.is_empty())
+    }
+
+    // block devices in /sys/block are symlinks into /sys/devices, the ones
+    // without backing hardware are linked below /sys/devices/virtual
+    fn is_virtual_device(&self, dir_entry: &DirEntry) -> bool {
+        kind::transport(&dir_entry.path()) == Transport::Virtual
+    }
+
+    /// Reads the configured sysfs tree and its sub-directories to determine and
+    /// return drives as a list of devices with partitions
+    pub fn get_devices(&self) -> Result<Vec<Device>, DrivesError> {
+        let mounts = self.mounts.read_resolved_mounts(&self.dev_path)?;
+        let mdstat = md::read_mdstat(&self.mdstat_path);
         let mut devices = vec![];
         for entry in fs_wrap::read_dir(&self.base_path)? {
             let entry = entry.map_err(|_err| DrivesError::DiraccessError {
                 directory: self.base_path.to_string(),
             })?;
 
+            if !self.include_virtual && self.is_virtual_device(&entry) {
+                continue;
+            }
+
+            // the filter is checked as early as possible, so nothing more is
+            // read for a device that is skipped anyway
             let device_name = fs_wrap::name_from_direntry(&entry)?;
+            // the paths of a multipath namespace are listed with their head
+            if nvme::is_multipath_path(&device_name) || !self.filter.matches_name(&device_name) {
+                continue;
+            }
 
             let removable_path = fs_wrap::build_path(&entry, "/removable")?;
             let removable = fs_wrap::read_bool_file(&removable_path)?;
+            if !self.filter.matches_removable(removable) {
+                continue;
+            }
 
-            let partitions = self.find_partitions(&entry)?;
+            let size = fs_wrap::read_file_to_u64(&build_path(&entry, "/size")?)?;
+            if !self.filter.matches_size(Size::new(size))
+                || (!self.include_unattached_loops
+                    && loop_device::is_unattached(&entry.path(), &device_name, size))
+            {
+                continue;
+            }
+
+            let mut partitions = self.find_partitions(&entry, &mounts)?;
+            let dev = fs_wrap::read_dev_file(&build_path(&entry, "/dev")?);
+            let mountpoints = self.find_mountpoints(&mounts, &device_name, dev);
+            if !self
+                .filter
+                .matches_mounted(filter::is_mounted(&mountpoints, &partitions))
+            {
+                continue;
+            }
 
+            for partition in partitions.iter_mut() {
+                partition.udev = self.read_udev_info(partition.dev);
+                partition.filesystem = self.probe_filesystem(&partition.name);
+            }
             let model_and_serial = self.read_model_and_serial_if_available(&entry);
-            let size = fs_wrap::read_file_to_u64(&build_path(&entry, "/size")?)?;
+            let transport = kind::transport(&entry.path());
+            let filesystem = if partitions.is_empty() {
+                self.probe_filesystem(&device_name)
+            } else {
+                None
+            };
 
             let mut device = Device {
                 name: device_name.clone(),
+                dev,
                 partitions,
+                holders: topology::read_links(&entry.path().join("holders")),
+                slaves: topology::read_links(&entry.path().join("slaves")),
                 is_removable: removable,
+                kind: kind::kind(&device_name, &entry.path(), transport),
+                transport,
+                is_hotpluggable: kind::is_hotpluggable(&entry.path(), transport),
+                usb: if transport == Transport::Usb {
+                    usb::read_usb_info(&entry.path())
+                } else {
+                    None
+                },
+                mmc: mmc::read_mmc_info(&entry.path()),
+                nvme: if nvme::is_namespace(&device_name) {
+                    nvme::read_nvme_info(&entry.path())
+                } else {
+                    None
+                },
+                dm: dm::read_dm_info(&entry.path(), &self.dev_path),
+                md: md::read_md_info(&entry.path(), &device_name, &mdstat),
+                loop_info: loop_device::read_loop_info(&entry.path()),
+                queue: queue::read_queue_info(&entry.path()),
                 model: model_and_serial.0,
                 serial: model_and_serial.1,
+                wwid: self.read_wwid(&entry),
                 size: Size::new(size),
+                mountpoints,
                 uuid: GptUUID::NotAvailable,
+                partition_table_type: None,
+                mbr: None,
+                udev: self.read_udev_info(dev),
+                filesystem,
             };
-            device = gpt::enrich_with_gpt_uuid(device);
+            if let Some(udev) = &device.udev {
+                // e.g. sd cards and some usb bridges have no model file in sysfs
+                device.model = device.model.or_else(|| udev.model.clone());
+                device.serial = device.serial.or_else(|| udev.serial_short.clone());
+                device.wwid = device.wwid.or_else(|| udev.wwn.clone());
+            }
+            if let Some(usb) = &device.usb {
+                device.serial = device.serial.or_else(|| usb.serial.clone());
+            }
+            if let Some(mmc) = &device.mmc {
+                // sd cards have a name instead of a model file
+                device.model = device.model.or_else(|| Some(mmc.name.clone()));
+                device.serial = device
+                    .serial
+                    .or_else(|| Some(format!("{:#010x}", mmc.serial)));
+            }
+            if let Some(nvme) = &device.nvme {
+                // a multipath namespace has no controller as device
+                device.model = device.model.or_else(|| nvme.model.clone());
+                device.serial = device.serial.or_else(|| nvme.serial.clone());
+            }
+            let sector_size = device
+                .queue
+                .as_ref()
+                .map_or(512, |queue| u64::from(queue.logical_block_size));
+            if self.read_gpt {
+                device = gpt::enrich_with_gpt_uuid(device, &self.dev_path, sector_size);
+            }
+            if self.read_mbr {
+                device = mbr::enrich_with_mbr(device, &self.dev_path, sector_size);
+            }
+            device.partition_table_type = self.detect_partition_table_type(&device);
             devices.push(device);
         }
         Ok(devices)
     }
-
-    fn new() -> Drives {
-        Drives {
-            base_path: "/sys/block".to_owned(),
-            mounts: Mounts::new(),
-        }
-    }
 }
 
 /// Reads /sys/block and its sub-directories to determine and return drives as a list of
 /// devices with partitions
 pub fn get_devices() -> Result<Vec<Device>, DrivesError> {
-    let drives = Drives::new();
+    let drives = Drives::default();
     drives.get_devices()
 }
 
@@ -189,11 +675,13 @@ mod tests {
 
     #[test]
     fn test_drives() {
-        // a temp dir to represent /sys/block
+        // a temp dir to represent /sys with a block subdir
         let temp_dir = tempdir().unwrap();
+        let block_dir_path = temp_dir.path().join("block");
+        fs::create_dir(&block_dir_path).unwrap();
 
         // a subdir for a device (in this example an nvme drive=
-        let next_dir_path = temp_dir.path().join("nvme0n1");
+        let next_dir_path = block_dir_path.join("nvme0n1");
         fs::create_dir(&next_dir_path).unwrap();
 
         // create a "removable" flag file for the device
@@ -208,35 +696,59 @@ mod tests {
         fs::create_dir(&part_one_dir_path).unwrap();
         size_file = fs::File::create(part_one_dir_path.as_path().join("size")).unwrap();
         size_file.write_all("1050624".as_bytes()).unwrap();
-        
-        let mut partition_file = fs::File::create(part_one_dir_path.as_path().join("partition")).unwrap();
-        partition_file.write_all("1".as_bytes()).unwrap();
-
+        fs::write(part_one_dir_path.join("dev"), "259:1").unwrap();
+        fs::write(part_one_dir_path.join("start"), "2048").unwrap();
 
+        let mut partition_file =
+            fs::File::create(part_one_dir_path.as_path().join("partition")).unwrap();
+        partition_file.write_all("1".as_bytes()).unwrap();
 
         let part_two_dir_path = next_dir_path.join("nvme0n1p2");
         fs::create_dir(&part_two_dir_path).unwrap();
         size_file = fs::File::create(part_two_dir_path.as_path().join("size")).unwrap();
         size_file.write_all("999162511".as_bytes()).unwrap();
-        let mut partition_file = fs::File::create(part_two_dir_path.as_path().join("partition")).unwrap();
+        let mut partition_file =
+            fs::File::create(part_two_dir_path.as_path().join("partition")).unwrap();
         partition_file.write_all("2".as_bytes()).unwrap();
 
-        
         // and create a third dir that isn't following the partition name schema
         // and should therefor not be identified as a partition
         let power_dir_path = next_dir_path.join("power");
         fs::create_dir(power_dir_path).unwrap();
 
         // execute
-        let drives = Drives {
-            base_path: temp_dir.path().to_str().unwrap().to_owned(),
-            mounts: Mounts::new(),
-        };
+        // a procfs tree with the first partition mounted and a subdirectory of it bind-mounted
+        let proc_dir_path = temp_dir.path().join("proc");
+        fs::create_dir_all(proc_dir_path.join("self")).unwrap();
+        fs::write(
+            proc_dir_path.join("self").join("mountinfo"),
+            concat!(
+                "26 1 259:1 / /boot/efi rw,relatime shared:1 - vfat /dev/nvme0n1p1 rw\n",
+                "27 1 259:1 /EFI /mnt/efi rw,relatime shared:1 - vfat /dev/nvme0n1p1 rw\n",
+            ),
+        )
+        .unwrap();
+
+        // a udev database with an entry for the first partition only
+        let udev_dir_path = temp_dir.path().join("udev");
+        fs::create_dir(&udev_dir_path).unwrap();
+        fs::write(
+            udev_dir_path.join("b259:1"),
+            "E:ID_FS_TYPE=vfat\nE:ID_FS_UUID=4A3B-1C2D\n",
+        )
+        .unwrap();
+
+        let drives = DrivesBuilder::new()
+            .sysfs_root(temp_dir.path())
+            .procfs_root(&proc_dir_path)
+            .dev_root(temp_dir.path().join("dev"))
+            .udev_data_dir(&udev_dir_path)
+            .build();
         let devices = drives.get_devices().unwrap();
 
         // now verify the results
         assert_eq!(1, devices.len());
-        let device = devices.get(0).unwrap();
+        let device = devices.first().unwrap();
         assert_eq!("nvme0n1", device.name);
         assert!(!device.is_removable);
         assert_eq!(2, device.partitions.len());
@@ -245,10 +757,273 @@ mod tests {
             .iter()
             .find(|part| part.name.eq("nvme0n1p1"));
         assert!(part1.is_some());
+        let mountpoints = &part1.unwrap().mountpoints;
+        assert_eq!(2, mountpoints.len());
+        let mount = &mountpoints[0];
+        assert_eq!("/boot/efi", mount.mountpoint);
+        assert_eq!("vfat", mount.filesystem);
+        assert_eq!("/EFI", mountpoints[1].root);
+        assert_eq!("/mnt/efi", mountpoints[1].mountpoint);
+        let udev = part1.unwrap().udev.as_ref().unwrap();
+        assert_eq!(Some("vfat".to_string()), udev.fs_type);
+        assert_eq!(Some("4A3B-1C2D".to_string()), udev.fs_uuid);
+        assert_eq!(Some(2048), part1.unwrap().start);
         let part2 = device
             .partitions
             .iter()
             .find(|part| part.name.eq("nvme0n1p2"));
         assert!(part2.is_some());
+        assert!(part2.unwrap().mountpoints.is_empty());
+        assert!(part2.unwrap().udev.is_none());
+        assert!(part2.unwrap().start.is_none());
+    }
+
+    #[test]
+    fn test_exclude_virtual_devices() {
+        let temp_dir = tempdir().unwrap();
+        let block_dir_path = temp_dir.path().join("block");
+        fs::create_dir(&block_dir_path).unwrap();
+
+        // like in the real sysfs the entries in block are symlinks into devices
+        for (name, parent) in [
+            (
+                "sda",
+                "devices/pci0000:00/0000:00:17.0/ata1/host0/target0:0:0/0:0:0:0/block",
+            ),
+            ("loop0", "devices/virtual/block"),
+        ] {
+            let device_dir_path = temp_dir.path().join(parent).join(name);
+            fs::create_dir_all(&device_dir_path).unwrap();
+            fs::write(device_dir_path.join("removable"), "0").unwrap();
+            fs::write(device_dir_path.join("size"), "2048").unwrap();
+            std::os::unix::fs::symlink(
+                PathBuf::from("..").join(parent).join(name),
+                block_dir_path.join(name),
+            )
+            .unwrap();
+        }
+
+        let mut drives = DrivesBuilder::new().sysfs_root(temp_dir.path()).build();
+        let mut devices = drives.get_devices().unwrap();
+        assert_eq!(2, devices.len());
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(DeviceKind::Loop, devices[0].kind);
+        assert_eq!(Transport::Virtual, devices[0].transport);
+        assert_eq!(DeviceKind::Disk, devices[1].kind);
+        assert_eq!(Transport::Sata, devices[1].transport);
+        assert!(!devices[1].is_hotpluggable);
+
+        drives = DrivesBuilder::new()
+            .sysfs_root(temp_dir.path())
+            .include_virtual(false)
+            .build();
+        devices = drives.get_devices().unwrap();
+        assert_eq!(1, devices.len());
+        assert_eq!("sda", devices[0].name);
+    }
+
+    #[test]
+    fn test_filter_during_walk() {
+        let temp_dir = tempdir().unwrap();
+        let block_dir_path = temp_dir.path().join("block");
+        for (name, removable, size) in [
+            ("sda", "0", "1000215216"),
+            ("sdb", "1", "30031872"),
+            ("sdc", "1", "0"),
+        ] {
+            let device_dir_path = block_dir_path.join(name);
+            fs::create_dir_all(&device_dir_path).unwrap();
+            fs::write(device_dir_path.join("removable"), removable).unwrap();
+            fs::write(device_dir_path.join("size"), size).unwrap();
+        }
+        let partition_dir_path = block_dir_path.join("sdb").join("sdb1");
+        fs::create_dir(&partition_dir_path).unwrap();
+        fs::write(partition_dir_path.join("size"), "30029824").unwrap();
+        fs::write(partition_dir_path.join("partition"), "1").unwrap();
+        fs::write(partition_dir_path.join("dev"), "8:17").unwrap();
+        // a device without the files needed to read it, excluded by its name
+        // before anything is read
+        fs::create_dir(block_dir_path.join("zram0")).unwrap();
+
+        let proc_dir_path = temp_dir.path().join("proc");
+        fs::create_dir_all(proc_dir_path.join("self")).unwrap();
+        fs::write(
+            proc_dir_path.join("self").join("mountinfo"),
+            "40 1 8:17 / /media/stick rw - vfat /dev/sdb1 rw\n",
+        )
+        .unwrap();
+
+        let devices_matching = |filter: DeviceFilter| {
+            let mut names: Vec<String> = DrivesBuilder::new()
+                .sysfs_root(temp_dir.path())
+                .procfs_root(&proc_dir_path)
+                .read_udev(false)
+                .filter(filter.exclude_name_prefixes(["zram"]))
+                .build()
+                .get_devices()
+                .unwrap()
+                .into_iter()
+                .map(|device| device.name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            vec!["sda", "sdb", "sdc"],
+            devices_matching(DeviceFilter::new())
+        );
+        assert_eq!(
+            vec!["sdb", "sdc"],
+            devices_matching(DeviceFilter::new().removable(true))
+        );
+        assert_eq!(
+            vec!["sda", "sdb"],
+            devices_matching(DeviceFilter::new().non_empty())
+        );
+        assert_eq!(
+            vec!["sdb"],
+            devices_matching(DeviceFilter::new().mounted(true))
+        );
+        assert_eq!(
+            vec!["sdc"],
+            devices_matching(DeviceFilter::new().removable(true).mounted(false))
+        );
+        assert_eq!(
+            vec!["sda"],
+            devices_matching(
+                DeviceFilter::new().min_size(Size::from_bytes(100 * 1024 * 1024 * 1024))
+            )
+        );
+    }
+
+    #[test]
+    fn test_emmc_with_boot_areas() {
+        let temp_dir = tempdir().unwrap();
+        let block_dir_path = temp_dir.path().join("block");
+        let device_dir_path = block_dir_path.join("mmcblk0");
+        let card_dir_path = device_dir_path.join("device");
+        fs::create_dir_all(&card_dir_path).unwrap();
+        fs::write(device_dir_path.join("removable"), "0").unwrap();
+        fs::write(device_dir_path.join("size"), "15269888").unwrap();
+        fs::write(
+            card_dir_path.join("cid"),
+            "150101384754463452061234567834b7",
+        )
+        .unwrap();
+        fs::write(card_dir_path.join("type"), "MMC").unwrap();
+        fs::write(card_dir_path.join("date"), "03/2017").unwrap();
+
+        let partition_dir_path = device_dir_path.join("mmcblk0p1");
+        fs::create_dir(&partition_dir_path).unwrap();
+        fs::write(partition_dir_path.join("size"), "15267840").unwrap();
+        fs::write(partition_dir_path.join("partition"), "1").unwrap();
+        // the boot areas are below the user area and listed in block as well
+        for name in ["mmcblk0boot0", "mmcblk0boot1"] {
+            for dir_path in [device_dir_path.join(name), block_dir_path.join(name)] {
+                fs::create_dir(&dir_path).unwrap();
+                fs::write(dir_path.join("removable"), "0").unwrap();
+                fs::write(dir_path.join("size"), "8192").unwrap();
+            }
+        }
+
+        let drives = DrivesBuilder::new()
+            .sysfs_root(temp_dir.path())
+            .read_udev(false)
+            .build();
+        let mut devices = drives.get_devices().unwrap();
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(3, devices.len());
+
+        let emmc = &devices[0];
+        assert_eq!("mmcblk0", emmc.name);
+        assert_eq!(DeviceKind::Disk, emmc.kind);
+        assert_eq!(1, emmc.partitions.len());
+        assert_eq!("mmcblk0p1", emmc.partitions[0].name);
+        assert_eq!(Some("8GTF4R".to_string()), emmc.model);
+        assert_eq!(Some("0x12345678".to_string()), emmc.serial);
+        let mmc = emmc.mmc.as_ref().unwrap();
+        assert_eq!(MmcCardType::Mmc, mmc.card_type);
+        assert_eq!(Some("Samsung".to_string()), mmc.manufacturer);
+        assert_eq!(2017, mmc.manufacture_year);
+
+        assert_eq!("mmcblk0boot0", devices[1].name);
+        assert_eq!(DeviceKind::MmcBoot, devices[1].kind);
+        assert!(devices[1].mmc.is_none());
+    }
+
+    #[test]
+    fn test_mbr_partition_table() {
+        let temp_dir = tempdir().unwrap();
+        let device_dir_path = temp_dir.path().join("block").join("sdb");
+        let partition_dir_path = device_dir_path.join("sdb1");
+        fs::create_dir_all(&partition_dir_path).unwrap();
+        fs::write(device_dir_path.join("removable"), "1").unwrap();
+        fs::write(device_dir_path.join("size"), "2048").unwrap();
+        fs::write(partition_dir_path.join("size"), "1024").unwrap();
+        fs::write(partition_dir_path.join("partition"), "1").unwrap();
+
+        // a dev tree with an image holding an MBR with one FAT32 partition
+        let dev_dir_path = temp_dir.path().join("dev");
+        fs::create_dir(&dev_dir_path).unwrap();
+        let mut image = vec![0u8; 1024 * 1024];
+        image[440..444].copy_from_slice(&0xcafe0001u32.to_le_bytes());
+        image[446 + 4] = 0x0c;
+        image[446 + 8..446 + 12].copy_from_slice(&1024u32.to_le_bytes());
+        image[446 + 12..446 + 16].copy_from_slice(&1024u32.to_le_bytes());
+        image[510] = 0x55;
+        image[511] = 0xaa;
+        fs::write(dev_dir_path.join("sdb"), image).unwrap();
+
+        let drives = DrivesBuilder::new()
+            .sysfs_root(temp_dir.path())
+            .dev_root(&dev_dir_path)
+            .read_udev(false)
+            .build();
+        let devices = drives.get_devices().unwrap();
+        let device = devices.first().unwrap();
+        assert_eq!(Some(PartitionTableType::Dos), device.partition_table_type);
+        assert_eq!("cafe0001", device.mbr.as_ref().unwrap().pt_uuid);
+        let partition = device.partitions[0].mbr.as_ref().unwrap();
+        assert_eq!("cafe0001-01", partition.part_uuid);
+        assert_eq!(1024, partition.start_lba);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let image = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test/gptdisk.img");
+        let mut device = image::inspect(std::path::Path::new(image)).unwrap();
+        device.dev = Some(DeviceNumber::new(7, 0));
+        device.serial = Some("4C530001".to_string());
+        device.mountpoints = vec![Mount {
+            device: "/dev/loop0".to_string(),
+            mountpoint: "/mnt/image dir".to_string(),
+            filesystem: "vfat".to_string(),
+            root: "/".to_string(),
+            dev: DeviceNumber::new(7, 0),
+            mount_id: 42,
+            options: MountOptions::parse("ro,noatime,uid=1000,flush"),
+        }];
+        device.udev = Some(UdevInfo {
+            fs_type: Some("vfat".to_string()),
+            symlinks: vec!["disk/by-label/IMAGE".to_string()],
+            ..UdevInfo::default()
+        });
+        device.filesystem = Some(FilesystemInfo {
+            fs_type: FilesystemType::Fat32,
+            label: Some("IMAGE".to_string()),
+            uuid: None,
+        });
+        let snapshot = vec![device];
+
+        let json = serde_json::to_string_pretty(&snapshot).unwrap();
+        let reloaded: Vec<Device> = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot, reloaded);
+        assert!(diff(&snapshot, &reloaded).is_empty());
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(1048576, value[0]["size"]["bytes"]);
+        assert_eq!(2048, value[0]["size"]["blocks"]);
+        assert_eq!(7, value[0]["dev"]["major"]);
     }
 }
@@ -0,0 +1,202 @@
+use std::path::Path;
+
+use crate::fs_wrap;
+
+/// What a loop device is used for, guessed from its backing file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LoopSource {
+    /// a squashfs image of a snap package (`/var/lib/snapd/snaps/*.snap`)
+    Snap,
+    /// an AppImage mounted through a loop device (type 1 AppImages)
+    AppImage,
+    /// any other file, e.g. a disk image attached by the user
+    Image,
+}
+
+/// The attributes of an attached loop device from `/sys/block/loopN/loop`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoopInfo {
+    /// the path of the backing file (without the ` (deleted)` suffix)
+    pub backing_file: String,
+    /// whether the backing file was deleted while attached
+    pub backing_file_deleted: bool,
+    /// the offset into the backing file in bytes
+    pub offset: u64,
+    /// the maximum size used of the backing file in bytes, 0 for the whole file
+    pub size_limit: u64,
+    /// whether the loop device is detached when it is closed the last time
+    pub autoclear: bool,
+    /// whether the kernel scans the loop device for partitions
+    pub partscan: bool,
+    /// whether direct I/O is used to access the backing file
+    pub direct_io: bool,
+    /// what the loop device is used for
+    pub source: LoopSource,
+}
+
+impl LoopInfo {
+    /// whether the loop device holds a snap package
+    pub fn is_snap(&self) -> bool {
+        self.source == LoopSource::Snap
+    }
+}
+
+impl LoopSource {
+    fn from_backing_file(backing_file: &str) -> LoopSource {
+        let path = Path::new(backing_file);
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("snap") => LoopSource::Snap,
+            _ if backing_file.starts_with("/var/lib/snapd/") => LoopSource::Snap,
+            Some("appimage") => LoopSource::AppImage,
+            _ => LoopSource::Image,
+        }
+    }
+}
+
+fn read_flag(loop_dir: &Path, file: &str) -> bool {
+    fs_wrap::read_bool_file(&fs_wrap::path_to_string(&loop_dir.join(file))).unwrap_or(false)
+}
+
+fn read_number(loop_dir: &Path, file: &str) -> u64 {
+    fs_wrap::read_file_to_u64(&fs_wrap::path_to_string(&loop_dir.join(file))).unwrap_or(0)
+}
+
+/// Reads `<device_dir>/loop`, which only exists while a file is attached
+pub(crate) fn read_loop_info(device_dir: &Path) -> Option<LoopInfo> {
+    let loop_dir = device_dir.join("loop");
+    let backing_file = fs_wrap::read_file_to_string(&loop_dir.join("backing_file")).ok()?;
+    let (backing_file, backing_file_deleted) = match backing_file.strip_suffix(" (deleted)") {
+        Some(backing_file) => (backing_file.to_string(), true),
+        None => (backing_file, false),
+    };
+    Some(LoopInfo {
+        source: LoopSource::from_backing_file(&backing_file),
+        backing_file,
+        backing_file_deleted,
+        offset: read_number(&loop_dir, "offset"),
+        size_limit: read_number(&loop_dir, "sizelimit"),
+        autoclear: read_flag(&loop_dir, "autoclear"),
+        partscan: read_flag(&loop_dir, "partscan"),
+        direct_io: read_flag(&loop_dir, "dio"),
+    })
+}
+
+/// Whether the device is a loop device without backing file, those have a size of 0
+pub(crate) fn is_unattached(device_dir: &Path, name: &str, size: u64) -> bool {
+    name.starts_with("loop") && size == 0 && !device_dir.join("loop").is_dir()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use crate::DrivesBuilder;
+
+    fn create_loop(block_dir: &Path, name: &str, size: &str, backing_file: Option<&str>) {
+        let device_dir = block_dir.join(name);
+        fs::create_dir_all(&device_dir).unwrap();
+        fs::write(device_dir.join("removable"), "0\n").unwrap();
+        fs::write(device_dir.join("size"), format!("{}\n", size)).unwrap();
+        if let Some(backing_file) = backing_file {
+            let loop_dir = device_dir.join("loop");
+            fs::create_dir(&loop_dir).unwrap();
+            for (file, content) in [
+                ("backing_file", backing_file),
+                ("offset", "1048576"),
+                ("sizelimit", "0"),
+                ("autoclear", "1"),
+                ("partscan", "0"),
+                ("dio", "0"),
+            ] {
+                fs::write(loop_dir.join(file), format!("{}\n", content)).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_loop_info() {
+        let temp_dir = tempdir().unwrap();
+        create_loop(
+            temp_dir.path(),
+            "loop0",
+            "2048",
+            Some("/home/user/disk.img"),
+        );
+
+        let info = read_loop_info(&temp_dir.path().join("loop0")).unwrap();
+        assert_eq!("/home/user/disk.img", info.backing_file);
+        assert!(!info.backing_file_deleted);
+        assert_eq!(1048576, info.offset);
+        assert_eq!(0, info.size_limit);
+        assert!(info.autoclear);
+        assert!(!info.partscan);
+        assert!(!info.direct_io);
+        assert_eq!(LoopSource::Image, info.source);
+
+        create_loop(temp_dir.path(), "loop1", "0", None);
+        assert_eq!(None, read_loop_info(&temp_dir.path().join("loop1")));
+    }
+
+    #[test]
+    fn test_loop_source() {
+        assert_eq!(
+            LoopSource::Snap,
+            LoopSource::from_backing_file("/var/lib/snapd/snaps/core22_1380.snap")
+        );
+        assert_eq!(
+            LoopSource::AppImage,
+            LoopSource::from_backing_file("/home/user/Applications/Tool-x86_64.AppImage")
+        );
+        assert_eq!(
+            LoopSource::Image,
+            LoopSource::from_backing_file("/srv/images/debian.iso")
+        );
+
+        let temp_dir = tempdir().unwrap();
+        create_loop(
+            temp_dir.path(),
+            "loop2",
+            "2048",
+            Some("/var/lib/snapd/snaps/firefox_4173.snap (deleted)"),
+        );
+        let info = read_loop_info(&temp_dir.path().join("loop2")).unwrap();
+        assert!(info.is_snap());
+        assert!(info.backing_file_deleted);
+        assert_eq!("/var/lib/snapd/snaps/firefox_4173.snap", info.backing_file);
+    }
+
+    #[test]
+    fn test_unattached_loops_hidden() {
+        let sysfs_root = tempdir().unwrap();
+        let block_dir = sysfs_root.path().join("block");
+        create_loop(&block_dir, "loop0", "2048", Some("/home/user/disk.img"));
+        create_loop(&block_dir, "loop1", "0", None);
+
+        let read_names = |include_unattached_loops| {
+            let mut names: Vec<String> = DrivesBuilder::new()
+                .sysfs_root(sysfs_root.path())
+                .read_udev(false)
+                .include_unattached_loops(include_unattached_loops)
+                .build()
+                .get_devices()
+                .unwrap()
+                .into_iter()
+                .map(|device| device.name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(vec!["loop0"], read_names(false));
+        assert_eq!(vec!["loop0", "loop1"], read_names(true));
+    }
+}
@@ -0,0 +1,499 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{error::DrivesError, fs_wrap::path_to_string, Device};
+
+/// The kind of partition table on a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionTableType {
+    /// GUID partition table
+    Gpt,
+    /// MBR (DOS) partition table
+    Dos,
+    /// no partition table
+    None,
+}
+
+impl PartitionTableType {
+    /// the name as used by blkid and udev (ID_PART_TABLE_TYPE)
+    pub fn name(&self) -> &str {
+        match *self {
+            PartitionTableType::Gpt => "gpt",
+            PartitionTableType::Dos => "dos",
+            PartitionTableType::None => "none",
+        }
+    }
+}
+
+/// A cylinder/head/sector address as stored in a partition entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chs {
+    pub cylinder: u16,
+    pub head: u8,
+    pub sector: u8,
+}
+
+impl Chs {
+    fn from_bytes(bytes: &[u8]) -> Chs {
+        Chs {
+            cylinder: u16::from(bytes[1] & 0xc0) << 2 | u16::from(bytes[2]),
+            head: bytes[0],
+            sector: bytes[1] & 0x3f,
+        }
+    }
+}
+
+/// A primary or logical partition of an MBR partition table
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MbrPartition {
+    /// the partition number as used by linux (1-4 primary, 5+ logical)
+    pub number: u32,
+    /// the partition type byte
+    pub partition_type: u8,
+    /// the name of the partition type like fdisk shows it
+    pub type_name: String,
+    /// the bootable (active) flag
+    pub bootable: bool,
+    /// the start address in CHS notation
+    pub start_chs: Chs,
+    /// the end address in CHS notation
+    pub end_chs: Chs,
+    /// the first sector, relative to the start of the disk
+    pub start_lba: u64,
+    /// the number of sectors
+    pub sectors: u64,
+    /// a logical partition inside an extended partition
+    pub is_logical: bool,
+    /// the PARTUUID (`<disk signature>-<number>`) the kernel accepts for `root=`
+    pub part_uuid: String,
+}
+
+impl MbrPartition {
+    /// is it an extended partition (container for logical partitions)
+    pub fn is_extended(&self) -> bool {
+        is_extended_type(self.partition_type)
+    }
+}
+
+/// The MBR (DOS) partition table of a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MbrInfo {
+    /// the 32 bit disk signature
+    pub disk_signature: u32,
+    /// the disk signature as hex string, known as PTUUID
+    pub pt_uuid: String,
+    /// primary, extended and logical partitions
+    pub partitions: Vec<MbrPartition>,
+    /// a protective MBR in front of a GUID partition table
+    pub is_protective: bool,
+    /// a hybrid MBR, GPT protective entry plus real partition entries
+    pub is_hybrid: bool,
+}
+
+pub(crate) const GPT_PROTECTIVE_TYPE: u8 = 0xee;
+// logical partitions are numbered from 5, a limit prevents endless loops in broken chains
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+fn is_extended_type(partition_type: u8) -> bool {
+    [0x05, 0x0f, 0x85].contains(&partition_type)
+}
+
+/// returns the name of an MBR partition type like fdisk does
+pub fn partition_type_name(partition_type: u8) -> &'static str {
+    match partition_type {
+        0x00 => "Empty",
+        0x01 => "FAT12",
+        0x04 => "FAT16 <32M",
+        0x05 => "Extended",
+        0x06 => "FAT16",
+        0x07 => "HPFS/NTFS/exFAT",
+        0x0b => "W95 FAT32",
+        0x0c => "W95 FAT32 (LBA)",
+        0x0e => "W95 FAT16 (LBA)",
+        0x0f => "W95 Ext'd (LBA)",
+        0x11 => "Hidden FAT12",
+        0x14 => "Hidden FAT16 <32M",
+        0x16 => "Hidden FAT16",
+        0x17 => "Hidden HPFS/NTFS",
+        0x1b => "Hidden W95 FAT32",
+        0x1c => "Hidden W95 FAT32 (LBA)",
+        0x1e => "Hidden W95 FAT16 (LBA)",
+        0x27 => "Hidden NTFS WinRE",
+        0x82 => "Linux swap / Solaris",
+        0x83 => "Linux",
+        0x85 => "Linux extended",
+        0x8e => "Linux LVM",
+        0xa5 => "FreeBSD",
+        0xa6 => "OpenBSD",
+        0xa8 => "Darwin UFS",
+        0xa9 => "NetBSD",
+        0xaf => "HFS / HFS+",
+        0xda => "Non-FS data",
+        0xee => "GPT",
+        0xef => "EFI (FAT-12/16/32)",
+        0xfd => "Linux raid autodetect",
+        _ => "Unknown",
+    }
+}
+
+struct Entry {
+    partition_type: u8,
+    bootable: bool,
+    start_chs: Chs,
+    end_chs: Chs,
+    start: u64,
+    sectors: u64,
+}
+
+// parses the four entries of an MBR or EBR, None if the signature or the
+// boot indicators are invalid (e.g. a FAT boot sector)
+fn parse_entries(sector: &[u8]) -> Option<Vec<Entry>> {
+    if sector.len() < 512 || sector[510..512] != [0x55, 0xaa] {
+        return None;
+    }
+    let mut entries = vec![];
+    for raw in sector[446..510].chunks_exact(16) {
+        if raw[0] != 0x00 && raw[0] != 0x80 {
+            return None;
+        }
+        entries.push(Entry {
+            partition_type: raw[4],
+            bootable: raw[0] == 0x80,
+            start_chs: Chs::from_bytes(&raw[1..4]),
+            end_chs: Chs::from_bytes(&raw[5..8]),
+            start: u64::from(u32::from_le_bytes(raw[8..12].try_into().ok()?)),
+            sectors: u64::from(u32::from_le_bytes(raw[12..16].try_into().ok()?)),
+        });
+    }
+    Some(entries)
+}
+
+fn read_sector<R: Read + Seek>(
+    reader: &mut R,
+    lba: u64,
+    sector_size: u64,
+) -> Result<Vec<u8>, DrivesError> {
+    let offset = lba
+        .checked_mul(sector_size)
+        .ok_or(DrivesError::ReadingPartitionTableFailed)?;
+    reader
+        .seek(SeekFrom::Start(offset))
+        .map_err(|_err| DrivesError::ReadingPartitionTableFailed)?;
+    let mut sector = Vec::with_capacity(512);
+    reader
+        .take(512)
+        .read_to_end(&mut sector)
+        .map_err(|_err| DrivesError::ReadingPartitionTableFailed)?;
+    Ok(sector)
+}
+
+fn to_partition(entry: &Entry, number: u32, start_lba: u64, signature: u32) -> MbrPartition {
+    MbrPartition {
+        number,
+        partition_type: entry.partition_type,
+        type_name: partition_type_name(entry.partition_type).to_owned(),
+        bootable: entry.bootable,
+        start_chs: entry.start_chs,
+        end_chs: entry.end_chs,
+        start_lba,
+        sectors: entry.sectors,
+        is_logical: number > 4,
+        part_uuid: format!("{:08x}-{:02x}", signature, number),
+    }
+}
+
+/// Reads the MBR partition table including the logical partitions of an
+/// extended partition, returns `None` if there is no valid MBR
+pub fn read_mbr<R: Read + Seek>(
+    reader: &mut R,
+    sector_size: u64,
+) -> Result<Option<MbrInfo>, DrivesError> {
+    let sector = read_sector(reader, 0, sector_size)?;
+    let entries = match parse_entries(&sector) {
+        Some(entries) => entries,
+        None => return Ok(None),
+    };
+    // a FAT boot sector without partitioning (superfloppy) has the signature
+    // and zeros where the entries would be
+    if entries.iter().all(|entry| entry.partition_type == 0) {
+        return Ok(None);
+    }
+    let disk_signature = u32::from_le_bytes([sector[440], sector[441], sector[442], sector[443]]);
+
+    let mut partitions = vec![];
+    let mut extended_start = None;
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.partition_type == 0 {
+            continue;
+        }
+        if is_extended_type(entry.partition_type) && extended_start.is_none() {
+            extended_start = Some(entry.start);
+        }
+        let number = index as u32 + 1;
+        partitions.push(to_partition(entry, number, entry.start, disk_signature));
+    }
+
+    // follow the chain of extended boot records, every EBR holds one logical
+    // partition (relative to the EBR) and a link to the next EBR (relative to
+    // the start of the extended partition)
+    if let Some(extended_start) = extended_start {
+        let mut visited = HashSet::new();
+        let mut ebr_lba = extended_start;
+        let mut number = 5;
+        while visited.insert(ebr_lba) && visited.len() <= MAX_LOGICAL_PARTITIONS {
+            let ebr = read_sector(reader, ebr_lba, sector_size)?;
+            let Some(ebr_entries) = parse_entries(&ebr) else {
+                break;
+            };
+            let logical = &ebr_entries[0];
+            if logical.partition_type != 0 && logical.sectors != 0 {
+                let start_lba = ebr_lba + logical.start;
+                partitions.push(to_partition(logical, number, start_lba, disk_signature));
+                number += 1;
+            }
+            let next = &ebr_entries[1];
+            if !is_extended_type(next.partition_type) || next.start == 0 {
+                break;
+            }
+            ebr_lba = extended_start + next.start;
+        }
+    }
+
+    let has_protective_entry = partitions
+        .iter()
+        .any(|partition| partition.partition_type == GPT_PROTECTIVE_TYPE);
+    let is_hybrid = has_protective_entry && partitions.len() > 1;
+    Ok(Some(MbrInfo {
+        disk_signature,
+        pt_uuid: format!("{:08x}", disk_signature),
+        partitions,
+        is_protective: has_protective_entry && !is_hybrid,
+        is_hybrid,
+    }))
+}
+
+// reads the MBR of the device node, partitions are matched by their number,
+// the protective entry in front of a GPT is no partition of its own.
+// When the device can't be read the partition table type is left untouched.
+pub fn enrich_with_mbr(mut device: Device, dev_dir: &Path, sector_size: u64) -> Device {
+    let path = dev_dir.join(&device.name);
+    let mbr = File::open(&path)
+        .map_err(|_err| DrivesError::FileAccessError {
+            filename: path_to_string(&path),
+        })
+        .and_then(|mut file| read_mbr(&mut file, sector_size));
+    match mbr {
+        Ok(Some(mbr)) => {
+            for partition in device.partitions.iter_mut() {
+                partition.mbr = mbr
+                    .partitions
+                    .iter()
+                    .filter(|mbr_partition| mbr_partition.partition_type != GPT_PROTECTIVE_TYPE)
+                    .find(|mbr_partition| mbr_partition.number == partition.number)
+                    .cloned();
+            }
+            device.partition_table_type = if mbr.is_protective || mbr.is_hybrid {
+                Some(PartitionTableType::Gpt)
+            } else {
+                Some(PartitionTableType::Dos)
+            };
+            device.mbr = Some(mbr);
+        }
+        Ok(None) => device.partition_table_type = Some(PartitionTableType::None),
+        Err(_) => {}
+    }
+    device
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn entry(bootable: bool, partition_type: u8, start: u32, sectors: u32) -> [u8; 16] {
+        let mut entry = [0u8; 16];
+        entry[0] = if bootable { 0x80 } else { 0x00 };
+        // CHS 0/32/33 for the start and 1023/254/63 for the end
+        entry[1..4].copy_from_slice(&[0x20, 0x21, 0x00]);
+        entry[4] = partition_type;
+        entry[5..8].copy_from_slice(&[0xfe, 0xff, 0xff]);
+        entry[8..12].copy_from_slice(&start.to_le_bytes());
+        entry[12..16].copy_from_slice(&sectors.to_le_bytes());
+        entry
+    }
+
+    fn write_table(image: &mut [u8], lba: usize, entries: &[[u8; 16]]) {
+        let sector = &mut image[lba * 512..(lba + 1) * 512];
+        for (index, entry) in entries.iter().enumerate() {
+            sector[446 + index * 16..446 + (index + 1) * 16].copy_from_slice(entry);
+        }
+        sector[510] = 0x55;
+        sector[511] = 0xaa;
+    }
+
+    #[test]
+    fn test_read_mbr_with_logical_partitions() {
+        let mut image = vec![0u8; 4096 * 512];
+        image[440..444].copy_from_slice(&0x0d3c6e4eu32.to_le_bytes());
+        write_table(
+            &mut image,
+            0,
+            &[
+                entry(true, 0x0c, 2048, 1024),
+                entry(false, 0x05, 3072, 1024),
+            ],
+        );
+        // first EBR at 3072 with a logical partition and a link to the second EBR
+        write_table(
+            &mut image,
+            3072,
+            &[entry(false, 0x83, 1, 255), entry(false, 0x05, 256, 512)],
+        );
+        // second EBR at 3072 + 256 with the last logical partition
+        write_table(&mut image, 3328, &[entry(false, 0x82, 2, 500)]);
+
+        let mbr = read_mbr(&mut Cursor::new(image), 512).unwrap().unwrap();
+        assert_eq!(0x0d3c6e4e, mbr.disk_signature);
+        assert_eq!("0d3c6e4e", mbr.pt_uuid);
+        assert!(!mbr.is_protective);
+        assert!(!mbr.is_hybrid);
+        assert_eq!(4, mbr.partitions.len());
+
+        let boot = &mbr.partitions[0];
+        assert_eq!(1, boot.number);
+        assert!(boot.bootable);
+        assert_eq!(0x0c, boot.partition_type);
+        assert_eq!("W95 FAT32 (LBA)", boot.type_name);
+        assert_eq!(2048, boot.start_lba);
+        assert_eq!(1024, boot.sectors);
+        assert_eq!("0d3c6e4e-01", boot.part_uuid);
+        assert_eq!(
+            Chs {
+                cylinder: 0,
+                head: 32,
+                sector: 33
+            },
+            boot.start_chs
+        );
+        assert_eq!(
+            Chs {
+                cylinder: 1023,
+                head: 254,
+                sector: 63
+            },
+            boot.end_chs
+        );
+
+        assert!(mbr.partitions[1].is_extended());
+        assert_eq!(2, mbr.partitions[1].number);
+
+        let root = &mbr.partitions[2];
+        assert_eq!(5, root.number);
+        assert!(root.is_logical);
+        assert_eq!("Linux", root.type_name);
+        assert_eq!(3073, root.start_lba);
+        assert_eq!("0d3c6e4e-05", root.part_uuid);
+
+        let swap = &mbr.partitions[3];
+        assert_eq!(6, swap.number);
+        assert_eq!(3330, swap.start_lba);
+        assert_eq!(500, swap.sectors);
+    }
+
+    #[test]
+    fn test_read_mbr_loop_in_chain() {
+        let mut image = vec![0u8; 2048 * 512];
+        write_table(&mut image, 0, &[entry(false, 0x0f, 1024, 1024)]);
+        // the link of the first EBR points to the second one at 1024 + 512
+        write_table(
+            &mut image,
+            1024,
+            &[entry(false, 0x83, 1, 10), entry(false, 0x05, 512, 10)],
+        );
+        // the link of the second EBR points back to itself
+        write_table(
+            &mut image,
+            1536,
+            &[entry(false, 0x83, 1, 10), entry(false, 0x05, 512, 10)],
+        );
+        let mbr = read_mbr(&mut Cursor::new(image), 512).unwrap().unwrap();
+        assert_eq!(3, mbr.partitions.len());
+        assert_eq!(6, mbr.partitions[2].number);
+    }
+
+    #[test]
+    fn test_read_protective_and_hybrid_mbr() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test/gptdisk.img");
+        let mbr = read_mbr(&mut File::open(path).unwrap(), 512)
+            .unwrap()
+            .unwrap();
+        assert!(mbr.is_protective);
+        assert!(!mbr.is_hybrid);
+        assert_eq!("GPT", mbr.partitions[0].type_name);
+
+        let mut image = vec![0u8; 512];
+        write_table(
+            &mut image,
+            0,
+            &[entry(false, 0xee, 1, 2047), entry(true, 0x0c, 2048, 1024)],
+        );
+        let mbr = read_mbr(&mut Cursor::new(image), 512).unwrap().unwrap();
+        assert!(!mbr.is_protective);
+        assert!(mbr.is_hybrid);
+    }
+
+    #[test]
+    fn test_enrich_skips_protective_entry() {
+        use crate::{Partition, PartitionTableType};
+
+        let device = Device {
+            partitions: vec![Partition::fixture("sda1", 1), Partition::fixture("sda2", 2)],
+            ..Device::fixture("gptdisk.img")
+        };
+        let dev_dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test"));
+        let device = enrich_with_mbr(device, dev_dir, 512);
+        assert_eq!(Some(PartitionTableType::Gpt), device.partition_table_type);
+        assert!(device.mbr.unwrap().is_protective);
+        assert!(device
+            .partitions
+            .iter()
+            .all(|partition| partition.mbr.is_none()));
+    }
+
+    #[test]
+    fn test_no_mbr() {
+        assert!(read_mbr(&mut Cursor::new(vec![0u8; 512]), 512)
+            .unwrap()
+            .is_none());
+        // the boot sector of a FAT16 superfloppy like written by mkfs.fat has
+        // the signature and zeros in the area of the partition entries
+        let mut boot_sector = vec![0u8; 512];
+        boot_sector[0..11].copy_from_slice(b"\xeb\x3c\x90mkfs.fat");
+        boot_sector[0x0b..0x0d].copy_from_slice(&512u16.to_le_bytes());
+        boot_sector[0x0d] = 4;
+        boot_sector[0x0e] = 4;
+        boot_sector[0x10] = 2;
+        boot_sector[0x11..0x13].copy_from_slice(&512u16.to_le_bytes());
+        boot_sector[0x13..0x15].copy_from_slice(&32768u16.to_le_bytes());
+        boot_sector[0x15] = 0xf8;
+        boot_sector[0x16..0x18].copy_from_slice(&32u16.to_le_bytes());
+        boot_sector[0x26] = 0x29;
+        boot_sector[0x2b..0x3e].copy_from_slice(b"FLOPPY     FAT16   ");
+        let message = b"This is not a bootable disk.";
+        boot_sector[0x5a..0x5a + message.len()].copy_from_slice(message);
+        boot_sector[510] = 0x55;
+        boot_sector[511] = 0xaa;
+        assert!(read_mbr(&mut Cursor::new(boot_sector), 512)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,477 @@
+use std::path::Path;
+
+use crate::{fs_wrap, Size};
+
+/// Progress of a resync, recovery, check or reshape of an md array
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MdSyncProgress {
+    /// the part of the array already processed
+    pub completed: Size,
+    /// the size to process
+    pub total: Size,
+}
+
+impl MdSyncProgress {
+    /// the completed part between 0.0 and 1.0
+    pub fn fraction(&self) -> f64 {
+        if self.total.get_raw_size() == 0 {
+            return 0.0;
+        }
+        self.completed.get_raw_size() as f64 / self.total.get_raw_size() as f64
+    }
+}
+
+/// A member device of an md array from `md/dev-<name>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MdMember {
+    /// the name of the member device, e.g. `sda1`
+    pub name: String,
+    /// the position in the array, None for spares and faulty devices
+    pub slot: Option<u32>,
+    /// the state flags, e.g. `in_sync`, `faulty`, `spare`, `write_mostly`, `blocked`
+    pub state: Vec<String>,
+    /// the number of read errors corrected on this device
+    pub errors: Option<u64>,
+}
+
+impl MdMember {
+    fn has_state(&self, flag: &str) -> bool {
+        self.state.iter().any(|state| state == flag)
+    }
+
+    /// whether the device is a fully synchronized part of the array
+    pub fn is_in_sync(&self) -> bool {
+        self.has_state("in_sync")
+    }
+
+    /// whether the device has failed and was kicked out of the array
+    pub fn is_faulty(&self) -> bool {
+        self.has_state("faulty")
+    }
+
+    /// whether the device is a spare (also while it is being rebuilt)
+    pub fn is_spare(&self) -> bool {
+        self.has_state("spare")
+    }
+}
+
+/// A member of an array as listed in `/proc/mdstat`, e.g. `sdb1[1](F)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MdStatMember {
+    /// the name of the member device
+    pub name: String,
+    /// the role number in brackets
+    pub role: u32,
+    /// marked with `(F)`
+    pub faulty: bool,
+    /// marked with `(S)`
+    pub spare: bool,
+}
+
+/// The entry of an array in `/proc/mdstat`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MdStat {
+    /// the name of the array, e.g. `md127`
+    pub name: String,
+    /// false for arrays which are assembled but not started
+    pub active: bool,
+    /// marked as `(read-only)` or `(auto-read-only)`
+    pub read_only: bool,
+    /// the personality, e.g. `raid1`, unknown for inactive arrays
+    pub level: Option<String>,
+    /// the member devices
+    pub members: Vec<MdStatMember>,
+    /// the number of devices the array should have (`[n/m]`)
+    pub raid_disks: Option<u32>,
+    /// the number of working devices (`[n/m]`)
+    pub working_disks: Option<u32>,
+    /// one character per slot, `U` for up and `_` for missing, e.g. `UU_`
+    pub status: Option<String>,
+    /// the running operation, e.g. `recovery`, `resync`, `check` or `reshape`
+    pub sync_action: Option<String>,
+    /// the progress of the running operation
+    pub sync_completed: Option<MdSyncProgress>,
+}
+
+impl MdStat {
+    /// whether slots of the array are missing
+    pub fn is_degraded(&self) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|status| status.contains('_'))
+            || self
+                .raid_disks
+                .zip(self.working_disks)
+                .is_some_and(|(raid_disks, working)| working < raid_disks)
+    }
+}
+
+/// State of a Linux software RAID (md) array from `/sys/block/mdX/md`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MdInfo {
+    /// the RAID level, e.g. `raid1`, `raid5`, `linear`
+    pub level: String,
+    /// the number of devices the array should have
+    pub raid_disks: u32,
+    /// the number of missing devices, 0 for levels without redundancy
+    pub degraded: u32,
+    /// the state of the array, e.g. `clean`, `active`, `readonly`, `inactive`
+    pub array_state: String,
+    /// the running or last operation: `idle`, `resync`, `recover`, `check`,
+    /// `repair`, `reshape` or `frozen`
+    pub sync_action: Option<String>,
+    /// the progress of the running operation, None when idle
+    pub sync_completed: Option<MdSyncProgress>,
+    /// the member devices
+    pub members: Vec<MdMember>,
+    /// the entry in `/proc/mdstat` as cross-check, if found
+    pub mdstat: Option<MdStat>,
+}
+
+impl MdInfo {
+    /// whether the array is missing devices, taken from the `degraded` count
+    /// and the status in `/proc/mdstat`
+    pub fn is_degraded(&self) -> bool {
+        self.degraded > 0 || self.mdstat.as_ref().is_some_and(MdStat::is_degraded)
+    }
+
+    /// the member devices which failed
+    pub fn faulty_members(&self) -> Vec<&MdMember> {
+        self.members
+            .iter()
+            .filter(|member| member.is_faulty())
+            .collect()
+    }
+}
+
+// sync_completed has the format `<done> / <total>` in sectors, or `none`
+fn parse_sync_completed(value: &str) -> Option<MdSyncProgress> {
+    let (completed, total) = value.split_once('/')?;
+    Some(MdSyncProgress {
+        completed: Size::new(completed.trim().parse().ok()?),
+        total: Size::new(total.trim().parse().ok()?),
+    })
+}
+
+fn read_optional(dir: &Path, file: &str) -> Option<String> {
+    fs_wrap::read_file_to_string(&dir.join(file))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+fn read_members(md_dir: &Path) -> Vec<MdMember> {
+    let mut members: Vec<MdMember> = std::fs::read_dir(md_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let file_name = entry.file_name().into_string().ok()?;
+                    let name = file_name.strip_prefix("dev-")?.to_string();
+                    let dir = entry.path();
+                    Some(MdMember {
+                        name,
+                        slot: read_optional(&dir, "slot").and_then(|slot| slot.parse().ok()),
+                        state: read_optional(&dir, "state")
+                            .map(|state| state.split(',').map(str::to_string).collect())
+                            .unwrap_or_default(),
+                        errors: read_optional(&dir, "errors")
+                            .and_then(|errors| errors.parse().ok()),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    members.sort_by(|a, b| a.name.cmp(&b.name));
+    members
+}
+
+/// Reads `<device_dir>/md`, None if the device is no md array
+pub(crate) fn read_md_info(device_dir: &Path, name: &str, mdstat: &[MdStat]) -> Option<MdInfo> {
+    let md_dir = device_dir.join("md");
+    let array_state = read_optional(&md_dir, "array_state")?;
+    Some(MdInfo {
+        level: read_optional(&md_dir, "level").unwrap_or_default(),
+        raid_disks: read_optional(&md_dir, "raid_disks")
+            .and_then(|disks| disks.parse().ok())
+            .unwrap_or(0),
+        degraded: read_optional(&md_dir, "degraded")
+            .and_then(|degraded| degraded.parse().ok())
+            .unwrap_or(0),
+        array_state,
+        sync_action: read_optional(&md_dir, "sync_action"),
+        sync_completed: read_optional(&md_dir, "sync_completed")
+            .and_then(|value| parse_sync_completed(&value)),
+        members: read_members(&md_dir),
+        mdstat: mdstat.iter().find(|stat| stat.name == name).cloned(),
+    })
+}
+
+// e.g. `sdb1[1](F)`
+fn parse_member(field: &str) -> Option<MdStatMember> {
+    let (name, rest) = field.split_once('[')?;
+    let (role, flags) = rest.split_once(']')?;
+    Some(MdStatMember {
+        name: name.to_string(),
+        role: role.parse().ok()?,
+        faulty: flags.contains("(F)"),
+        spare: flags.contains("(S)"),
+    })
+}
+
+// the lines following the array line, e.g.
+// `1953382464 blocks super 1.2 [2/1] [U_]` and
+// `[==>....]  recovery = 12.6% (246420480/1953382464) finish=...`
+fn parse_detail_line(stat: &mut MdStat, line: &str) {
+    let mut fields = line.split_whitespace().peekable();
+    while let Some(field) = fields.next() {
+        if let Some(inner) = field.strip_prefix('[').and_then(|f| f.strip_suffix(']')) {
+            if let Some((raid_disks, working)) = inner.split_once('/') {
+                stat.raid_disks = raid_disks.parse().ok();
+                stat.working_disks = working.parse().ok();
+            } else if !inner.is_empty() && inner.chars().all(|c| c == 'U' || c == '_') {
+                stat.status = Some(inner.to_string());
+            }
+        } else if fields.peek() == Some(&"=") {
+            stat.sync_action = Some(field.to_string());
+            // skip `=` and the percentage, the progress follows in KiB
+            let progress = fields.nth(2).and_then(|progress| {
+                let (completed, total) = progress
+                    .strip_prefix('(')?
+                    .strip_suffix(')')?
+                    .split_once('/')?;
+                Some(MdSyncProgress {
+                    completed: Size::new(completed.parse::<u64>().ok()? * 2),
+                    total: Size::new(total.parse::<u64>().ok()? * 2),
+                })
+            });
+            stat.sync_completed = progress;
+        }
+    }
+}
+
+/// Parses the content of `/proc/mdstat`, see md(4)
+pub(crate) fn parse_mdstat(content: &str) -> Vec<MdStat> {
+    let mut arrays: Vec<MdStat> = vec![];
+    for line in content.lines() {
+        if line.starts_with(char::is_whitespace) {
+            if let Some(stat) = arrays.last_mut() {
+                parse_detail_line(stat, line);
+            }
+            continue;
+        }
+        let Some((name, rest)) = line.split_once(" : ") else {
+            continue;
+        };
+        if !name.starts_with("md") {
+            continue;
+        }
+        let mut fields = rest.split_whitespace().peekable();
+        let active = fields.next() == Some("active");
+        let mut read_only = false;
+        while let Some(field) = fields.peek() {
+            if !field.starts_with('(') {
+                break;
+            }
+            read_only |= field.contains("read-only");
+            fields.next();
+        }
+        // inactive arrays have no personality, only members
+        let level = if active {
+            fields.next().map(str::to_string)
+        } else {
+            None
+        };
+        let mut members: Vec<MdStatMember> = fields.filter_map(parse_member).collect();
+        members.sort_by_key(|member| member.role);
+        arrays.push(MdStat {
+            name: name.trim().to_string(),
+            active,
+            read_only,
+            level,
+            members,
+            raid_disks: None,
+            working_disks: None,
+            status: None,
+            sync_action: None,
+            sync_completed: None,
+        });
+    }
+    arrays
+}
+
+/// Reads and parses the mdstat file, empty if it does not exist (no md driver loaded)
+pub(crate) fn read_mdstat(path: &Path) -> Vec<MdStat> {
+    std::fs::read_to_string(path)
+        .map(|content| parse_mdstat(&content))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use crate::DrivesBuilder;
+
+    const MDSTAT: &str = "Personalities : [raid1] [raid6] [raid5] [raid4]
+md1 : active raid5 sdf1[3] sde1[2](F) sdd1[1] sdc1[0]
+      1953260544 blocks super 1.2 level 5, 512k chunk, algorithm 2 [3/2] [UU_]
+      [==>..................]  recovery = 12.6% (123136000/976630272) finish=77.3min speed=183984K/sec
+      bitmap: 2/8 pages [8KB], 65536KB chunk
+
+md0 : active (auto-read-only) raid1 sdb1[1] sda1[0]
+      976630336 blocks super 1.2 [2/2] [UU]
+
+md127 : inactive sdg1[0](S)
+      976630336 blocks super 1.2
+
+unused devices: <none>
+";
+
+    #[test]
+    fn test_parse_mdstat() {
+        let arrays = parse_mdstat(MDSTAT);
+        assert_eq!(3, arrays.len());
+
+        let md1 = &arrays[0];
+        assert_eq!("md1", md1.name);
+        assert!(md1.active);
+        assert_eq!(Some("raid5".to_string()), md1.level);
+        assert_eq!(
+            vec!["sdc1", "sdd1", "sde1", "sdf1"],
+            md1.members
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(md1.members[2].faulty);
+        assert_eq!((Some(3), Some(2)), (md1.raid_disks, md1.working_disks));
+        assert_eq!(Some("UU_".to_string()), md1.status);
+        assert!(md1.is_degraded());
+        assert_eq!(Some("recovery".to_string()), md1.sync_action);
+        let progress = md1.sync_completed.unwrap();
+        assert_eq!(123136000 * 1024, progress.completed.get_bytes());
+        assert_eq!(976630272 * 1024, progress.total.get_bytes());
+
+        let md0 = &arrays[1];
+        assert!(md0.read_only);
+        assert_eq!(Some("raid1".to_string()), md0.level);
+        assert!(!md0.is_degraded());
+        assert_eq!(None, md0.sync_action);
+
+        let md127 = &arrays[2];
+        assert!(!md127.active);
+        assert_eq!(None, md127.level);
+        assert!(md127.members[0].spare);
+    }
+
+    fn create_member(md_dir: &Path, name: &str, slot: &str, state: &str) {
+        let dir = md_dir.join(format!("dev-{}", name));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("slot"), format!("{}\n", slot)).unwrap();
+        fs::write(dir.join("state"), format!("{}\n", state)).unwrap();
+        fs::write(dir.join("errors"), "0\n").unwrap();
+    }
+
+    #[test]
+    fn test_read_md_info() {
+        let temp_dir = tempdir().unwrap();
+        let md_dir = temp_dir.path().join("md1").join("md");
+        fs::create_dir_all(&md_dir).unwrap();
+        for (file, content) in [
+            ("level", "raid5\n"),
+            ("raid_disks", "3\n"),
+            ("degraded", "1\n"),
+            ("array_state", "clean\n"),
+            ("sync_action", "recover\n"),
+            ("sync_completed", "246272000 / 1953260544\n"),
+        ] {
+            fs::write(md_dir.join(file), content).unwrap();
+        }
+        create_member(&md_dir, "sdc1", "0", "in_sync");
+        create_member(&md_dir, "sdd1", "1", "in_sync");
+        create_member(&md_dir, "sde1", "none", "faulty");
+        create_member(&md_dir, "sdf1", "2", "spare");
+
+        let info =
+            read_md_info(&temp_dir.path().join("md1"), "md1", &parse_mdstat(MDSTAT)).unwrap();
+        assert_eq!("raid5", info.level);
+        assert_eq!(3, info.raid_disks);
+        assert!(info.is_degraded());
+        assert_eq!("clean", info.array_state);
+        assert_eq!(Some("recover".to_string()), info.sync_action);
+        let progress = info.sync_completed.unwrap();
+        assert_eq!(246272000, progress.completed.get_raw_size());
+        assert!((progress.fraction() - 0.126).abs() < 0.001);
+        assert_eq!(4, info.members.len());
+        assert_eq!(None, info.members[2].slot);
+        assert!(info.members[0].is_in_sync());
+        assert!(info.members[3].is_spare());
+        assert_eq!(
+            vec!["sde1"],
+            info.faulty_members()
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(3), info.mdstat.unwrap().raid_disks);
+
+        assert_eq!(None, read_md_info(temp_dir.path(), "md1", &[]));
+    }
+
+    #[test]
+    fn test_idle_array_without_mdstat() {
+        let temp_dir = tempdir().unwrap();
+        let md_dir = temp_dir.path().join("md0").join("md");
+        fs::create_dir_all(&md_dir).unwrap();
+        fs::write(md_dir.join("level"), "raid1\n").unwrap();
+        fs::write(md_dir.join("raid_disks"), "2\n").unwrap();
+        fs::write(md_dir.join("degraded"), "0\n").unwrap();
+        fs::write(md_dir.join("array_state"), "active\n").unwrap();
+        fs::write(md_dir.join("sync_action"), "idle\n").unwrap();
+        fs::write(md_dir.join("sync_completed"), "none\n").unwrap();
+
+        let info = read_md_info(&temp_dir.path().join("md0"), "md0", &[]).unwrap();
+        assert!(!info.is_degraded());
+        assert_eq!(None, info.sync_completed);
+        assert_eq!(None, info.mdstat);
+        assert!(info.members.is_empty());
+        assert!(read_mdstat(&temp_dir.path().join("mdstat")).is_empty());
+    }
+
+    #[test]
+    fn test_get_devices_with_md_array() {
+        let sysfs_root = tempdir().unwrap();
+        let device_dir = sysfs_root.path().join("block").join("md1");
+        fs::create_dir_all(device_dir.join("md")).unwrap();
+        fs::write(device_dir.join("removable"), "0\n").unwrap();
+        fs::write(device_dir.join("size"), "3906521088\n").unwrap();
+        fs::write(device_dir.join("md").join("array_state"), "clean\n").unwrap();
+        fs::write(device_dir.join("md").join("level"), "raid5\n").unwrap();
+
+        let procfs_root = tempdir().unwrap();
+        fs::create_dir(procfs_root.path().join("self")).unwrap();
+        fs::write(procfs_root.path().join("self").join("mountinfo"), "").unwrap();
+        fs::write(procfs_root.path().join("mdstat"), MDSTAT).unwrap();
+
+        let devices = DrivesBuilder::new()
+            .sysfs_root(sysfs_root.path())
+            .procfs_root(procfs_root.path())
+            .read_udev(false)
+            .build()
+            .get_devices()
+            .unwrap();
+        let md = devices[0].md.as_ref().unwrap();
+        assert_eq!("raid5", md.level);
+        // the degraded count is missing, the mdstat status shows the missing slot
+        assert!(md.is_degraded());
+    }
+}
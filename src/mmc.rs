@@ -0,0 +1,258 @@
+use std::path::Path;
+
+use crate::fs_wrap;
+
+/// The type of a card in an MMC/SD slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MmcCardType {
+    /// SD, SDHC or SDXC card
+    Sd,
+    /// MMC card or soldered eMMC
+    Mmc,
+    /// SDIO card (e.g. wifi), combo cards also have a memory part
+    Sdio,
+    /// a type not known by this crate
+    Unknown,
+}
+
+/// Identification of an SD/MMC card from its CID (card identification)
+/// register, see the SD and JEDEC eMMC specifications for the layouts
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MmcInfo {
+    /// the type of the card
+    pub card_type: MmcCardType,
+    /// the manufacturer ID (MID) assigned by the SD association or JEDEC
+    pub manufacturer_id: u8,
+    /// the manufacturer name for well-known manufacturer IDs
+    pub manufacturer: Option<String>,
+    /// the OEM/application ID, two ASCII characters on SD cards (e.g. `SD`),
+    /// the hex value (e.g. `0x01`) on MMC
+    pub oem_id: String,
+    /// the product name (5 characters on SD, 6 on MMC)
+    pub name: String,
+    /// the hardware revision (upper nibble of PRV)
+    pub hardware_revision: u8,
+    /// the firmware revision (lower nibble of PRV)
+    pub firmware_revision: u8,
+    /// the product serial number
+    pub serial: u32,
+    /// the year of manufacture
+    pub manufacture_year: u16,
+    /// the month of manufacture (1-12)
+    pub manufacture_month: u8,
+    /// the raw CID register as hex string
+    pub cid: String,
+    /// the raw CSD (card specific data) register as hex string, if available
+    pub csd: Option<String>,
+}
+
+impl MmcCardType {
+    fn from_sysfs(card_type: &str) -> MmcCardType {
+        match card_type {
+            "SD" => MmcCardType::Sd,
+            "MMC" => MmcCardType::Mmc,
+            "SDIO" | "SDcombo" => MmcCardType::Sdio,
+            _ => MmcCardType::Unknown,
+        }
+    }
+}
+
+// manufacturer IDs of the SD association are not published, these are the
+// ones commonly found on cards
+fn sd_manufacturer(id: u8) -> Option<&'static str> {
+    Some(match id {
+        0x01 => "Panasonic",
+        0x02 => "Toshiba",
+        0x03 => "SanDisk",
+        0x1b => "Samsung",
+        0x1d => "ADATA",
+        0x27 => "Phison",
+        0x28 => "Lexar",
+        0x31 => "Silicon Power",
+        0x41 => "Kingston",
+        0x74 => "Transcend",
+        0x76 => "Patriot",
+        0x82 => "Sony",
+        _ => return None,
+    })
+}
+
+// manufacturer IDs assigned by JEDEC for MMC and eMMC
+fn mmc_manufacturer(id: u8) -> Option<&'static str> {
+    Some(match id {
+        0x11 => "Toshiba",
+        0x13 => "Micron",
+        0x15 => "Samsung",
+        0x45 => "SanDisk",
+        0x70 => "Kingston",
+        0x90 => "SK Hynix",
+        0xfe => "Micron",
+        _ => return None,
+    })
+}
+
+// extracts the bits [high:low] of the 128 bit register
+fn bits(cid: u128, high: u32, low: u32) -> u128 {
+    (cid >> low) & ((1u128 << (high - low + 1)) - 1)
+}
+
+fn ascii(cid: u128, high: u32, low: u32) -> String {
+    let value = bits(cid, high, low);
+    let len = (high - low + 1) / 8;
+    (0..len)
+        .rev()
+        .map(|index| ((value >> (index * 8)) & 0xff) as u8)
+        .filter(|byte| byte.is_ascii_graphic() || *byte == b' ')
+        .map(char::from)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Decodes a CID register given as 32 hex digits like in sysfs, the layout
+/// depends on the card type
+pub(crate) fn decode_cid(cid: &str, card_type: MmcCardType) -> Option<MmcInfo> {
+    let raw = cid.trim();
+    if raw.len() != 32 {
+        return None;
+    }
+    let value = u128::from_str_radix(raw, 16).ok()?;
+    let manufacturer_id = bits(value, 127, 120) as u8;
+    let info = if card_type == MmcCardType::Mmc {
+        MmcInfo {
+            card_type,
+            manufacturer_id,
+            manufacturer: mmc_manufacturer(manufacturer_id).map(str::to_string),
+            oem_id: format!("{:#04x}", bits(value, 111, 104)),
+            name: ascii(value, 103, 56),
+            hardware_revision: bits(value, 55, 52) as u8,
+            firmware_revision: bits(value, 51, 48) as u8,
+            serial: bits(value, 47, 16) as u32,
+            // 4 bits only, newer devices (EXT_CSD_REV > 4) start at 2013 instead
+            // of 1997, the date file of the kernel has the corrected year
+            manufacture_year: 1997 + bits(value, 11, 8) as u16,
+            manufacture_month: bits(value, 15, 12) as u8,
+            cid: raw.to_lowercase(),
+            csd: None,
+        }
+    } else {
+        MmcInfo {
+            card_type,
+            manufacturer_id,
+            manufacturer: sd_manufacturer(manufacturer_id).map(str::to_string),
+            oem_id: ascii(value, 119, 104),
+            name: ascii(value, 103, 64),
+            hardware_revision: bits(value, 63, 60) as u8,
+            firmware_revision: bits(value, 59, 56) as u8,
+            serial: bits(value, 55, 24) as u32,
+            manufacture_year: 2000 + bits(value, 19, 12) as u16,
+            manufacture_month: bits(value, 11, 8) as u8,
+            cid: raw.to_lowercase(),
+            csd: None,
+        }
+    };
+    Some(info)
+}
+
+// the date file has the format MM/YYYY
+fn parse_date(date: &str) -> Option<(u16, u8)> {
+    let (month, year) = date.split_once('/')?;
+    Some((year.parse().ok()?, month.parse().ok()?))
+}
+
+/// Reads the CID (and CSD) of the card from `<device_dir>/device`, None if the
+/// device is no MMC/SD card
+pub(crate) fn read_mmc_info(device_dir: &Path) -> Option<MmcInfo> {
+    let card_dir = device_dir.join("device");
+    let cid = fs_wrap::read_file_to_string(&card_dir.join("cid")).ok()?;
+    let card_type = fs_wrap::read_file_to_string(&card_dir.join("type"))
+        .map_or(MmcCardType::Unknown, |card_type| {
+            MmcCardType::from_sysfs(&card_type)
+        });
+    let mut info = decode_cid(&cid, card_type)?;
+    info.csd = fs_wrap::read_file_to_string(&card_dir.join("csd"))
+        .ok()
+        .filter(|csd| !csd.is_empty());
+    // the kernel knows the correct year offset of eMMC devices
+    if let Some((year, month)) = fs_wrap::read_file_to_string(&card_dir.join("date"))
+        .ok()
+        .and_then(|date| parse_date(&date))
+    {
+        info.manufacture_year = year;
+        info.manufacture_month = month;
+    }
+    Some(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_decode_sd_cid() {
+        // SanDisk "SC64G", PRV 8.0, serial 0x1a2b3c4d, manufactured 08/2021
+        let info = decode_cid("0353445343363447801a2b3c4d0158f5", MmcCardType::Sd).unwrap();
+        assert_eq!(0x03, info.manufacturer_id);
+        assert_eq!(Some("SanDisk".to_string()), info.manufacturer);
+        assert_eq!("SD", info.oem_id);
+        assert_eq!("SC64G", info.name);
+        assert_eq!(8, info.hardware_revision);
+        assert_eq!(0, info.firmware_revision);
+        assert_eq!(0x1a2b3c4d, info.serial);
+        assert_eq!(2021, info.manufacture_year);
+        assert_eq!(8, info.manufacture_month);
+    }
+
+    #[test]
+    fn test_decode_mmc_cid() {
+        // Samsung eMMC "8GTF4R", PRV 0.6, serial 0x12345678, month 3 of year 4
+        let info = decode_cid("150101384754463452061234567834b7", MmcCardType::Mmc).unwrap();
+        assert_eq!(0x15, info.manufacturer_id);
+        assert_eq!(Some("Samsung".to_string()), info.manufacturer);
+        assert_eq!("0x01", info.oem_id);
+        assert_eq!("8GTF4R", info.name);
+        assert_eq!(0, info.hardware_revision);
+        assert_eq!(6, info.firmware_revision);
+        assert_eq!(0x12345678, info.serial);
+        assert_eq!(2001, info.manufacture_year);
+        assert_eq!(3, info.manufacture_month);
+    }
+
+    #[test]
+    fn test_decode_invalid_cid() {
+        assert_eq!(None, decode_cid("0353445343", MmcCardType::Sd));
+        assert_eq!(
+            None,
+            decode_cid("zz53445343363447801a2b3c4d0158f5", MmcCardType::Sd)
+        );
+    }
+
+    #[test]
+    fn test_read_mmc_info() {
+        let temp_dir = tempdir().unwrap();
+        let card_dir = temp_dir.path().join("mmcblk0").join("device");
+        fs::create_dir_all(&card_dir).unwrap();
+        fs::write(card_dir.join("cid"), "150101384754463452061234567834b7\n").unwrap();
+        fs::write(card_dir.join("csd"), "d0270132075940000ffffff92400000f\n").unwrap();
+        fs::write(card_dir.join("type"), "MMC\n").unwrap();
+        fs::write(card_dir.join("date"), "03/2017\n").unwrap();
+
+        let info = read_mmc_info(&temp_dir.path().join("mmcblk0")).unwrap();
+        assert_eq!(MmcCardType::Mmc, info.card_type);
+        assert_eq!("8GTF4R", info.name);
+        assert_eq!(2017, info.manufacture_year);
+        assert_eq!(3, info.manufacture_month);
+        assert_eq!(
+            Some("d0270132075940000ffffff92400000f".to_string()),
+            info.csd
+        );
+
+        assert_eq!(None, read_mmc_info(temp_dir.path()));
+    }
+}
@@ -0,0 +1,606 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{error::DrivesError, Device, Drives, Mount, Partition, Size};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+// the multicast group the kernel sends its uevents to (udev uses group 2)
+const KERNEL_UEVENT_GROUP: u32 = 1;
+
+/// A change of the block devices or their mounts reported by [`DriveMonitor`]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DriveEvent {
+    /// a new block device appeared, e.g. a usb stick was plugged in
+    DeviceAdded(Device),
+    /// the block device with the given name disappeared
+    DeviceRemoved(String),
+    /// a partition appeared on a known device, e.g. after repartitioning
+    PartitionAdded {
+        device: String,
+        partition: Partition,
+    },
+    /// the partition with the given name disappeared from a known device
+    PartitionRemoved { device: String, partition: String },
+    /// the size of a known device changed, e.g. a card was inserted into or
+    /// removed from a card reader
+    MediaChanged(Device),
+    /// a device or partition (by name) was mounted
+    Mounted { name: String, mount: Mount },
+    /// a device or partition (by name) was unmounted
+    Unmounted { name: String, mount: Mount },
+}
+
+/// A source of notifications that the block devices or the mounts may have changed
+///
+/// The [`DriveMonitor`] re-reads the devices whenever [`MonitorBackend::wait`]
+/// returns true, so spurious notifications are harmless.
+pub trait MonitorBackend: Send {
+    /// blocks until a change may have happened (true) or the timeout elapsed
+    /// (false), waits without a limit if the timeout is None
+    fn wait(&mut self, timeout: Option<Duration>) -> Result<bool, DrivesError>;
+}
+
+/// Backend that reports a possible change after every interval
+#[derive(Debug)]
+pub struct PollingBackend {
+    interval: Duration,
+}
+
+impl PollingBackend {
+    pub fn new(interval: Duration) -> PollingBackend {
+        PollingBackend { interval }
+    }
+}
+
+impl MonitorBackend for PollingBackend {
+    fn wait(&mut self, timeout: Option<Duration>) -> Result<bool, DrivesError> {
+        match timeout {
+            Some(timeout) if timeout < self.interval => {
+                thread::sleep(timeout);
+                Ok(false)
+            }
+            _ => {
+                thread::sleep(self.interval);
+                Ok(true)
+            }
+        }
+    }
+}
+
+// waits for one of the events of the descriptors, returns false if the timeout
+// elapsed or the call was interrupted by a signal
+pub(crate) fn poll(
+    fds: &mut [libc::pollfd],
+    timeout: Option<Duration>,
+) -> Result<bool, DrivesError> {
+    // rounded up, a timeout must not end before the given duration
+    let timeout_ms = timeout
+        .map(|timeout| i32::try_from(timeout.as_micros().div_ceil(1000)).unwrap_or(i32::MAX))
+        .unwrap_or(-1);
+    // SAFETY: fds is a valid array of pollfd structs of the given length
+    let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout_ms) };
+    if result < 0 {
+        return if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+            Ok(false)
+        } else {
+            Err(DrivesError::MonitorFailed)
+        };
+    }
+    Ok(result > 0)
+}
+
+/// Backend listening on the kernel uevent netlink socket for events of the
+/// block subsystem and polling the mount table for changes
+///
+/// Inside a container or another network namespace the socket can be opened,
+/// but the kernel sends no uevents to it. Use [`NetlinkBackend::rescan_interval`]
+/// to report a possible change periodically as well, or a [`PollingBackend`].
+#[derive(Debug)]
+pub struct NetlinkBackend {
+    socket: OwnedFd,
+    mountinfo: File,
+    rescan_interval: Option<Duration>,
+    last_rescan: Instant,
+}
+
+impl NetlinkBackend {
+    /// opens the uevent socket, `mountinfo` is the mount table to watch
+    /// (usually `/proc/self/mountinfo`)
+    pub fn new(mountinfo: &Path) -> Result<NetlinkBackend, DrivesError> {
+        let mountinfo = File::open(mountinfo).map_err(|_err| DrivesError::ReadingMountsFailed)?;
+        // SAFETY: plain socket(2) call, the returned descriptor is checked below
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        if fd < 0 {
+            return Err(DrivesError::MonitorFailed);
+        }
+        // SAFETY: fd is a freshly opened socket owned by nobody else
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: sockaddr_nl is a plain C struct, all zero is a valid value
+        let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        address.nl_groups = KERNEL_UEVENT_GROUP;
+        // SAFETY: address is a valid sockaddr_nl and the length matches its size
+        let result = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(DrivesError::MonitorFailed);
+        }
+        Ok(NetlinkBackend {
+            socket,
+            mountinfo,
+            rescan_interval: None,
+            last_rescan: Instant::now(),
+        })
+    }
+
+    /// reports a possible change also when there was no event for the given
+    /// interval (default none), catches changes without uevents
+    pub fn rescan_interval(mut self, interval: Duration) -> NetlinkBackend {
+        self.rescan_interval = Some(interval);
+        self
+    }
+
+    fn is_rescan_due(&self) -> bool {
+        self.rescan_interval
+            .is_some_and(|interval| self.last_rescan.elapsed() >= interval)
+    }
+
+    // reads all queued uevents, returns true if one of them is about a block
+    // device or if events were lost
+    fn drain_block_events(&self) -> bool {
+        let mut buffer = [0u8; 8192];
+        let mut is_block_event = false;
+        loop {
+            // SAFETY: the buffer is valid for writes of its full length
+            let len = unsafe {
+                libc::recv(
+                    self.socket.as_raw_fd(),
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                    libc::MSG_DONTWAIT,
+                )
+            };
+            if len < 0 {
+                match io::Error::last_os_error().kind() {
+                    io::ErrorKind::WouldBlock => return is_block_event,
+                    io::ErrorKind::Interrupted => continue,
+                    // ENOBUFS: the socket buffer overflowed during a burst of
+                    // events, only a full re-read catches up with the lost ones
+                    _ => return true,
+                }
+            }
+            if len == 0 {
+                return is_block_event;
+            }
+            // the message is a header (`ACTION@DEVPATH`) followed by nul
+            // separated `KEY=value` pairs
+            is_block_event |= buffer[..len as usize]
+                .split(|&b| b == 0)
+                .any(|field| field == b"SUBSYSTEM=block");
+        }
+    }
+}
+
+impl MonitorBackend for NetlinkBackend {
+    fn wait(&mut self, timeout: Option<Duration>) -> Result<bool, DrivesError> {
+        let mut fds = [
+            libc::pollfd {
+                fd: self.socket.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            // the mount table signals a change with POLLPRI (and POLLERR)
+            libc::pollfd {
+                fd: self.mountinfo.as_raw_fd(),
+                events: libc::POLLPRI,
+                revents: 0,
+            },
+        ];
+        // wake up for the next periodic rescan at the latest
+        let until_rescan = self
+            .rescan_interval
+            .map(|interval| interval.saturating_sub(self.last_rescan.elapsed()));
+        let timeout = match (timeout, until_rescan) {
+            (Some(timeout), Some(until_rescan)) => Some(timeout.min(until_rescan)),
+            (timeout, until_rescan) => timeout.or(until_rescan),
+        };
+        let changed = if poll(&mut fds, timeout)? {
+            let mounts_changed = fds[1].revents & (libc::POLLPRI | libc::POLLERR) != 0;
+            let block_event = fds[0].revents & libc::POLLIN != 0 && self.drain_block_events();
+            mounts_changed || block_event
+        } else {
+            false
+        };
+        if changed || self.is_rescan_due() {
+            self.last_rescan = Instant::now();
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+// the parts of a device that are compared between two snapshots
+#[derive(Debug)]
+struct DeviceState {
+    name: String,
+    size: Size,
+    partitions: Vec<(String, Size)>,
+    // the mounts of the device and its partitions with their name
+    mounts: Vec<(String, Mount)>,
+}
+
+impl DeviceState {
+    fn has_partition(&self, name: &str, size: Size) -> bool {
+        self.partitions
+            .iter()
+            .any(|partition| partition.0 == name && partition.1 == size)
+    }
+
+    fn has_mount(&self, name: &str, mount: &Mount) -> bool {
+        self.mounts
+            .iter()
+            .any(|(mount_name, other)| mount_name == name && other.mount_id == mount.mount_id)
+    }
+}
+
+impl From<&Device> for DeviceState {
+    fn from(device: &Device) -> Self {
+        let device_mounts = device
+            .mountpoints
+            .iter()
+            .map(|mount| (device.name.clone(), mount.clone()));
+        let partition_mounts = device.partitions.iter().flat_map(|partition| {
+            partition
+                .mountpoints
+                .iter()
+                .map(|mount| (partition.name.clone(), mount.clone()))
+        });
+        DeviceState {
+            name: device.name.clone(),
+            size: device.size,
+            partitions: device
+                .partitions
+                .iter()
+                .map(|partition| (partition.name.clone(), partition.size))
+                .collect(),
+            mounts: device_mounts.chain(partition_mounts).collect(),
+        }
+    }
+}
+
+// compares the new devices with the previous snapshot, a partition that changed
+// its size is reported as removed and added again
+fn diff_devices(old: &[DeviceState], devices: Vec<Device>) -> (Vec<DeviceState>, Vec<DriveEvent>) {
+    let states: Vec<DeviceState> = devices.iter().map(DeviceState::from).collect();
+    let mut events: Vec<DriveEvent> = old
+        .iter()
+        .filter(|old| !states.iter().any(|state| state.name == old.name))
+        .map(|old| DriveEvent::DeviceRemoved(old.name.clone()))
+        .collect();
+
+    for (device, state) in devices.into_iter().zip(&states) {
+        let Some(old) = old.iter().find(|old| old.name == state.name) else {
+            events.push(DriveEvent::DeviceAdded(device));
+            continue;
+        };
+        let mut device_events = vec![];
+        for (name, size) in &old.partitions {
+            if !state.has_partition(name, *size) {
+                device_events.push(DriveEvent::PartitionRemoved {
+                    device: state.name.clone(),
+                    partition: name.clone(),
+                });
+            }
+        }
+        for partition in &device.partitions {
+            if !old.has_partition(&partition.name, partition.size) {
+                device_events.push(DriveEvent::PartitionAdded {
+                    device: state.name.clone(),
+                    partition: partition.clone(),
+                });
+            }
+        }
+        for (name, mount) in &old.mounts {
+            if !state.has_mount(name, mount) {
+                device_events.push(DriveEvent::Unmounted {
+                    name: name.clone(),
+                    mount: mount.clone(),
+                });
+            }
+        }
+        for (name, mount) in &state.mounts {
+            if !old.has_mount(name, mount) {
+                device_events.push(DriveEvent::Mounted {
+                    name: name.clone(),
+                    mount: mount.clone(),
+                });
+            }
+        }
+        if old.size != state.size {
+            events.push(DriveEvent::MediaChanged(device));
+        }
+        events.append(&mut device_events);
+    }
+    (states, events)
+}
+
+/// Watches the block devices of a [`Drives`] instance and reports changes as [`DriveEvent`]s
+///
+/// Every notification of the backend leads to a full re-read of the devices
+/// which is compared with the previous state. The udev database is usually
+/// updated a bit later than sysfs, so the udev properties of added devices may
+/// be missing.
+///
+/// ```no_run
+/// let mut monitor = drives::DriveMonitor::new(drives::Drives::default()).unwrap();
+/// for event in monitor.by_ref() {
+///     println!("{:?}", event.unwrap());
+/// }
+/// ```
+pub struct DriveMonitor {
+    drives: Drives,
+    backend: Box<dyn MonitorBackend>,
+    devices: Vec<DeviceState>,
+    pending: VecDeque<DriveEvent>,
+}
+
+impl DriveMonitor {
+    /// creates a monitor listening on the kernel uevent socket, polling every
+    /// second is used if the socket can't be opened
+    ///
+    /// In a container or network namespace the socket usually opens fine, but
+    /// no uevents arrive, so the devices are also re-read every 30 seconds.
+    /// Pass a [`PollingBackend`] to [`DriveMonitor::with_backend`] to get
+    /// changes there sooner.
+    pub fn new(drives: Drives) -> Result<DriveMonitor, DrivesError> {
+        let backend: Box<dyn MonitorBackend> =
+            match NetlinkBackend::new(Path::new(drives.mounts.mount_file_path())) {
+                Ok(backend) => Box::new(backend.rescan_interval(DEFAULT_RESCAN_INTERVAL)),
+                Err(_) => Box::new(PollingBackend::new(DEFAULT_POLL_INTERVAL)),
+            };
+        DriveMonitor::with_backend(drives, backend)
+    }
+
+    /// creates a monitor with the given backend, the current devices are the
+    /// starting point and are not reported as added
+    pub fn with_backend(
+        drives: Drives,
+        backend: Box<dyn MonitorBackend>,
+    ) -> Result<DriveMonitor, DrivesError> {
+        let devices = drives.get_devices()?;
+        Ok(DriveMonitor {
+            drives,
+            backend,
+            devices: devices.iter().map(DeviceState::from).collect(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    fn refresh(&mut self) -> Result<(), DrivesError> {
+        let devices = self.drives.get_devices()?;
+        let (states, events) = diff_devices(&self.devices, devices);
+        self.devices = states;
+        self.pending.extend(events);
+        Ok(())
+    }
+
+    /// blocks until the next event
+    pub fn next_event(&mut self) -> Result<DriveEvent, DrivesError> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+            if self.backend.wait(None)? {
+                self.refresh()?;
+            }
+        }
+    }
+
+    /// blocks until the next event or the timeout elapsed (None)
+    pub fn next_event_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<DriveEvent>, DrivesError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if self.backend.wait(Some(remaining))? {
+                self.refresh()?;
+            }
+            if self.pending.is_empty() && Instant::now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl Iterator for DriveMonitor {
+    type Item = Result<DriveEvent, DrivesError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_event())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, path::PathBuf};
+
+    use tempfile::{tempdir, TempDir};
+
+    use crate::DrivesBuilder;
+
+    // reports a possible change immediately, the tests mutate the tree in between
+    struct ImmediateBackend;
+
+    impl MonitorBackend for ImmediateBackend {
+        fn wait(&mut self, _timeout: Option<Duration>) -> Result<bool, DrivesError> {
+            Ok(true)
+        }
+    }
+
+    fn create_block_dir(path: &Path, size: u64, dev: &str) {
+        fs::create_dir_all(path).unwrap();
+        fs::write(path.join("size"), size.to_string()).unwrap();
+        fs::write(path.join("dev"), dev).unwrap();
+        fs::write(path.join("removable"), "1").unwrap();
+    }
+
+    fn create_partition_dir(path: &Path, size: u64, dev: &str, number: u32) {
+        create_block_dir(path, size, dev);
+        fs::write(path.join("partition"), number.to_string()).unwrap();
+    }
+
+    fn create_monitor(root: &TempDir) -> DriveMonitor {
+        let drives = DrivesBuilder::new()
+            .sysfs_root(root.path().join("sys"))
+            .procfs_root(root.path().join("proc"))
+            .dev_root(root.path().join("dev"))
+            .read_gpt(false)
+            .read_mbr(false)
+            .read_udev(false)
+            .build();
+        DriveMonitor::with_backend(drives, Box::new(ImmediateBackend)).unwrap()
+    }
+
+    fn next(monitor: &mut DriveMonitor) -> Option<DriveEvent> {
+        monitor.next_event_timeout(Duration::ZERO).unwrap()
+    }
+
+    #[test]
+    fn test_monitor_events() {
+        let root = tempdir().unwrap();
+        let block_dir: PathBuf = root.path().join("sys").join("block");
+        let mountinfo = root.path().join("proc").join("self").join("mountinfo");
+        fs::create_dir_all(mountinfo.parent().unwrap()).unwrap();
+        fs::write(&mountinfo, "").unwrap();
+        // an empty card reader
+        create_block_dir(&block_dir.join("sda"), 0, "8:0");
+
+        let mut monitor = create_monitor(&root);
+        assert!(next(&mut monitor).is_none());
+
+        // a usb stick is plugged in
+        create_block_dir(&block_dir.join("sdb"), 30031872, "8:16");
+        create_partition_dir(&block_dir.join("sdb").join("sdb1"), 30029824, "8:17", 1);
+        match next(&mut monitor) {
+            Some(DriveEvent::DeviceAdded(device)) => {
+                assert_eq!("sdb", device.name);
+                assert_eq!(1, device.partitions.len());
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(next(&mut monitor).is_none());
+
+        // a card is inserted into the reader
+        fs::write(block_dir.join("sda").join("size"), "62333952").unwrap();
+        create_partition_dir(&block_dir.join("sda").join("sda1"), 62325760, "8:1", 1);
+        match next(&mut monitor) {
+            Some(DriveEvent::MediaChanged(device)) => assert_eq!("sda", device.name),
+            event => panic!("unexpected event {:?}", event),
+        }
+        match next(&mut monitor) {
+            Some(DriveEvent::PartitionAdded { device, partition }) => {
+                assert_eq!("sda", device);
+                assert_eq!("sda1", partition.name);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // the partition of the stick is mounted and unmounted again
+        fs::write(
+            &mountinfo,
+            "40 25 8:17 / /media/stick rw,nosuid,nodev - vfat /dev/sdb1 rw\n",
+        )
+        .unwrap();
+        match next(&mut monitor) {
+            Some(DriveEvent::Mounted { name, mount }) => {
+                assert_eq!("sdb1", name);
+                assert_eq!("/media/stick", mount.mountpoint);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        fs::write(&mountinfo, "").unwrap();
+        match next(&mut monitor) {
+            Some(DriveEvent::Unmounted { name, mount }) => {
+                assert_eq!("sdb1", name);
+                assert_eq!(40, mount.mount_id);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // the card is removed from the reader and the stick is unplugged
+        fs::remove_dir_all(block_dir.join("sda").join("sda1")).unwrap();
+        fs::write(block_dir.join("sda").join("size"), "0").unwrap();
+        fs::remove_dir_all(block_dir.join("sdb")).unwrap();
+        match next(&mut monitor) {
+            Some(DriveEvent::DeviceRemoved(name)) => assert_eq!("sdb", name),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(matches!(
+            next(&mut monitor),
+            Some(DriveEvent::MediaChanged(_))
+        ));
+        match next(&mut monitor) {
+            Some(DriveEvent::PartitionRemoved { device, partition }) => {
+                assert_eq!("sda", device);
+                assert_eq!("sda1", partition);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(next(&mut monitor).is_none());
+    }
+
+    #[test]
+    fn test_polling_backend_timeout() {
+        let mut backend = PollingBackend::new(Duration::from_secs(60));
+        assert!(!backend.wait(Some(Duration::from_millis(1))).unwrap());
+        let mut backend = PollingBackend::new(Duration::from_millis(1));
+        assert!(backend.wait(None).unwrap());
+    }
+
+    #[test]
+    fn test_netlink_backend_rescan() {
+        let temp_dir = tempdir().unwrap();
+        let mountinfo = temp_dir.path().join("mountinfo");
+        fs::write(&mountinfo, "").unwrap();
+        // netlink sockets may be unavailable in the build environment
+        let Ok(backend) = NetlinkBackend::new(&mountinfo) else {
+            return;
+        };
+        let mut backend = backend.rescan_interval(Duration::from_millis(20));
+        // the rescan is reported although the timeout is longer, uevents of
+        // other subsystems may wake up the backend in between
+        let start = Instant::now();
+        while !backend.wait(Some(Duration::from_secs(10))).unwrap() {}
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // the interval starts again after a rescan
+        assert!(!backend.wait(Some(Duration::from_millis(1))).unwrap());
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+/// The options a filesystem is mounted with
+///
+/// The generic (VFS) flags are available as typed fields, everything else is
+/// collected in [`MountOptions::filesystem_options`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MountOptions {
+    /// mounted read-only (`ro`), either the mount or the superblock
+    pub read_only: bool,
+    /// set-user-ID and set-group-ID bits are ignored (`nosuid`)
+    pub nosuid: bool,
+    /// device files are not interpreted (`nodev`)
+    pub nodev: bool,
+    /// execution of programs is not allowed (`noexec`)
+    pub noexec: bool,
+    /// all I/O is done synchronously (`sync`)
+    pub synchronous: bool,
+    /// directory updates are done synchronously (`dirsync`)
+    pub dirsync: bool,
+    /// access times are not updated (`noatime`)
+    pub noatime: bool,
+    /// access times of directories are not updated (`nodiratime`)
+    pub nodiratime: bool,
+    /// access times are only updated relative to modify or change time (`relatime`)
+    pub relatime: bool,
+    /// access times are always updated (`strictatime`)
+    pub strictatime: bool,
+    /// timestamps are only kept in memory (`lazytime`)
+    pub lazytime: bool,
+    /// filesystem specific options like `subvol=/@home`, `uid=1000` or `ssd`,
+    /// the value is `None` for options without a `=`
+    pub filesystem_options: BTreeMap<String, Option<String>>,
+}
+
+impl MountOptions {
+    /// parses a comma separated option string as found in `/proc/mounts`
+    pub fn parse(options: &str) -> MountOptions {
+        let mut mount_options = MountOptions::default();
+        mount_options.apply(options.split(','));
+        mount_options
+    }
+
+    /// combines the per-mount and the per-superblock options of a mountinfo entry
+    pub fn from_mountinfo(mount_options: &[String], super_options: &[String]) -> MountOptions {
+        let mut options = MountOptions::default();
+        options.apply(mount_options.iter().map(|option| option.as_str()));
+        options.apply(super_options.iter().map(|option| option.as_str()));
+        options
+    }
+
+    fn apply<'a>(&mut self, options: impl Iterator<Item = &'a str>) {
+        for option in options.filter(|option| !option.is_empty()) {
+            match option {
+                "ro" => self.read_only = true,
+                "rw" => {}
+                "nosuid" => self.nosuid = true,
+                "nodev" => self.nodev = true,
+                "noexec" => self.noexec = true,
+                "sync" => self.synchronous = true,
+                "dirsync" => self.dirsync = true,
+                "noatime" => self.noatime = true,
+                "nodiratime" => self.nodiratime = true,
+                "relatime" => self.relatime = true,
+                "strictatime" => self.strictatime = true,
+                "lazytime" => self.lazytime = true,
+                _ => {
+                    let (key, value) = match option.split_once('=') {
+                        Some((key, value)) => (key, Some(value.to_owned())),
+                        None => (option, None),
+                    };
+                    self.filesystem_options.insert(key.to_owned(), value);
+                }
+            }
+        }
+    }
+
+    /// returns the value of a filesystem specific `key=value` option
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.filesystem_options.get(key)?.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        let options = MountOptions::parse("ro,nosuid,nodev,relatime,uid=1000,umask=077,flush");
+        assert!(options.read_only);
+        assert!(options.nosuid);
+        assert!(options.nodev);
+        assert!(!options.noexec);
+        assert!(options.relatime);
+        assert!(!options.noatime);
+        assert_eq!(Some("1000"), options.get("uid"));
+        assert_eq!(Some("077"), options.get("umask"));
+        assert_eq!(Some(&None), options.filesystem_options.get("flush"));
+        assert_eq!(3, options.filesystem_options.len());
+    }
+
+    #[test]
+    fn test_from_mountinfo() {
+        let mount_options = vec!["rw".to_owned(), "noexec".to_owned(), "noatime".to_owned()];
+        let super_options = vec![
+            "ro".to_owned(),
+            "ssd".to_owned(),
+            "subvol=/@home".to_owned(),
+        ];
+        let options = MountOptions::from_mountinfo(&mount_options, &super_options);
+        assert!(options.read_only);
+        assert!(options.noexec);
+        assert!(options.noatime);
+        assert_eq!(Some("/@home"), options.get("subvol"));
+        assert!(options.filesystem_options.contains_key("ssd"));
+    }
+}
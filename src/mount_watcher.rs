@@ -0,0 +1,240 @@
+use std::{
+    fs::File,
+    os::fd::AsRawFd,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{error::DrivesError, monitor, mounts::Mounts, Mount, DEFAULT_PROCFS_ROOT};
+
+/// A change of the mount table reported by [`MountWatcher`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MountChange {
+    /// a filesystem was mounted
+    Added(Mount),
+    /// a filesystem was unmounted
+    Removed(Mount),
+    /// a mount got other options, e.g. by `mount -o remount,ro`
+    Remounted { old: Mount, new: Mount },
+}
+
+// a mount id is only unique while mounted, a reused id for another device or
+// location is a different mount
+fn is_same_mount(old: &Mount, new: &Mount) -> bool {
+    old.mount_id == new.mount_id && old.dev == new.dev && old.mountpoint == new.mountpoint
+}
+
+/// Compares two snapshots of the mount table
+pub fn diff_mounts(old: &[Mount], new: &[Mount]) -> Vec<MountChange> {
+    let mut changes: Vec<MountChange> = old
+        .iter()
+        .filter(|old| !new.iter().any(|new| is_same_mount(old, new)))
+        .map(|old| MountChange::Removed(old.clone()))
+        .collect();
+    for new in new {
+        match old.iter().find(|old| is_same_mount(old, new)) {
+            None => changes.push(MountChange::Added(new.clone())),
+            Some(old) if old.options != new.options => changes.push(MountChange::Remounted {
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    changes
+}
+
+/// Watches the mount table for changes
+///
+/// The kernel signals every change of the mount table as POLLPRI/POLLERR on
+/// `/proc/self/mounts` and `/proc/self/mountinfo`, the watcher waits for that
+/// and compares the re-read table with the previous one.
+///
+/// ```no_run
+/// let mut watcher = drives::MountWatcher::new().unwrap();
+/// loop {
+///     for change in watcher.wait().unwrap() {
+///         println!("{:?}", change);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MountWatcher {
+    mounts: Mounts,
+    mountinfo: File,
+    current: Vec<Mount>,
+}
+
+impl MountWatcher {
+    /// creates a watcher for `/proc/self/mountinfo`
+    pub fn new() -> Result<MountWatcher, DrivesError> {
+        MountWatcher::with_procfs_root(Path::new(DEFAULT_PROCFS_ROOT))
+    }
+
+    /// creates a watcher for the mountinfo file below the given procfs root
+    pub fn with_procfs_root(procfs_root: &Path) -> Result<MountWatcher, DrivesError> {
+        let mounts = Mounts::new(procfs_root);
+        let mountinfo = File::open(mounts.mount_file_path())
+            .map_err(|_err| DrivesError::ReadingMountsFailed)?;
+        let current = mounts.read_mountpoints()?;
+        Ok(MountWatcher {
+            mounts,
+            mountinfo,
+            current,
+        })
+    }
+
+    /// the mounts as of the last read
+    pub fn mounts(&self) -> &[Mount] {
+        &self.current
+    }
+
+    /// re-reads the mount table without waiting and returns the changes
+    /// since the last read
+    pub fn refresh(&mut self) -> Result<Vec<MountChange>, DrivesError> {
+        let mounts = self.mounts.read_mountpoints()?;
+        let changes = diff_mounts(&self.current, &mounts);
+        self.current = mounts;
+        Ok(changes)
+    }
+
+    /// blocks until the mount table changed
+    pub fn wait(&mut self) -> Result<Vec<MountChange>, DrivesError> {
+        loop {
+            let changes = self.wait_for_changes(None)?;
+            if !changes.is_empty() {
+                return Ok(changes);
+            }
+        }
+    }
+
+    /// blocks until the mount table changed or the timeout elapsed, the
+    /// returned changes are empty in the latter case
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Result<Vec<MountChange>, DrivesError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let changes = self.wait_for_changes(Some(remaining))?;
+            if !changes.is_empty() || Instant::now() >= deadline {
+                return Ok(changes);
+            }
+        }
+    }
+
+    fn wait_for_changes(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<MountChange>, DrivesError> {
+        let mut fds = [libc::pollfd {
+            fd: self.mountinfo.as_raw_fd(),
+            events: libc::POLLPRI,
+            revents: 0,
+        }];
+        if monitor::poll(&mut fds, timeout)?
+            && fds[0].revents & (libc::POLLPRI | libc::POLLERR) != 0
+        {
+            self.refresh()
+        } else {
+            Ok(vec![])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    fn read_snapshot(procfs_root: &Path, content: &str) -> Vec<Mount> {
+        fs::write(procfs_root.join("self").join("mountinfo"), content).unwrap();
+        Mounts::new(procfs_root).read_mountpoints().unwrap()
+    }
+
+    #[test]
+    fn test_diff_mounts() {
+        let procfs_root = tempdir().unwrap();
+        fs::create_dir(procfs_root.path().join("self")).unwrap();
+        let old = read_snapshot(
+            procfs_root.path(),
+            concat!(
+                "26 1 259:2 / / rw,relatime - ext4 /dev/nvme0n1p2 rw\n",
+                "40 26 8:17 / /media/stick rw,nosuid,nodev - vfat /dev/sdb1 rw,uid=1000\n",
+                "41 26 8:33 / /media/card rw - exfat /dev/sdc1 rw\n",
+            ),
+        );
+        let new = read_snapshot(
+            procfs_root.path(),
+            concat!(
+                "26 1 259:2 / / rw,relatime - ext4 /dev/nvme0n1p2 rw\n",
+                "40 26 8:17 / /media/stick ro,nosuid,nodev - vfat /dev/sdb1 ro,uid=1000\n",
+                "41 26 8:49 / /media/usb rw - exfat /dev/sdd1 rw\n",
+            ),
+        );
+
+        let changes = diff_mounts(&old, &new);
+        assert_eq!(3, changes.len());
+        match &changes[0] {
+            MountChange::Removed(mount) => assert_eq!("/media/card", mount.mountpoint),
+            change => panic!("unexpected change {:?}", change),
+        }
+        match &changes[1] {
+            MountChange::Remounted { old, new } => {
+                assert_eq!("/media/stick", new.mountpoint);
+                assert!(!old.options.read_only);
+                assert!(new.options.read_only);
+            }
+            change => panic!("unexpected change {:?}", change),
+        }
+        match &changes[2] {
+            MountChange::Added(mount) => {
+                assert_eq!(41, mount.mount_id);
+                assert_eq!("/media/usb", mount.mountpoint);
+            }
+            change => panic!("unexpected change {:?}", change),
+        }
+        assert!(diff_mounts(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_watcher_refresh() {
+        let procfs_root = tempdir().unwrap();
+        fs::create_dir(procfs_root.path().join("self")).unwrap();
+        let mountinfo = procfs_root.path().join("self").join("mountinfo");
+        fs::write(&mountinfo, "26 1 259:2 / / rw - ext4 /dev/nvme0n1p2 rw\n").unwrap();
+
+        let mut watcher = MountWatcher::with_procfs_root(procfs_root.path()).unwrap();
+        assert_eq!(1, watcher.mounts().len());
+        // a regular file never signals a change
+        assert!(watcher
+            .wait_timeout(Duration::from_millis(1))
+            .unwrap()
+            .is_empty());
+
+        fs::write(
+            &mountinfo,
+            concat!(
+                "26 1 259:2 / / rw - ext4 /dev/nvme0n1p2 rw\n",
+                "40 26 8:17 / /media/stick rw - vfat /dev/sdb1 rw\n",
+            ),
+        )
+        .unwrap();
+        let changes = watcher.refresh().unwrap();
+        assert_eq!(1, changes.len());
+        assert!(matches!(&changes[0], MountChange::Added(mount) if mount.mount_id == 40));
+        assert_eq!(2, watcher.mounts().len());
+        assert!(watcher.refresh().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_watcher_missing_mountinfo() {
+        let procfs_root = tempdir().unwrap();
+        assert!(matches!(
+            MountWatcher::with_procfs_root(procfs_root.path()),
+            Err(DrivesError::ReadingMountsFailed)
+        ));
+    }
+}
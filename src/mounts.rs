@@ -1,7 +1,112 @@
-use crate::{error::DrivesError, fs_wrap::read_lines};
+use std::{
+    fs,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::Path,
+};
+
+use crate::{
+    device_number::DeviceNumber,
+    error::DrivesError,
+    fs_wrap::read_lines,
+    mount_options::MountOptions,
+    usage::{self, FilesystemUsage},
+};
+
+/// A single entry of `/proc/self/mountinfo`
+///
+/// See proc(5) for the meaning of the fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MountInfo {
+    /// unique id of the mount
+    pub mount_id: u32,
+    /// id of the parent mount (or of itself for the root of the mount tree)
+    pub parent_id: u32,
+    /// major:minor of the device the filesystem lives on (`st_dev`)
+    pub dev: DeviceNumber,
+    /// the directory within the filesystem forming the root of this mount,
+    /// differs from `/` for bind mounts and btrfs subvolumes
+    pub root: String,
+    /// the path of the mount point
+    pub mountpoint: String,
+    /// per-mount options
+    pub mount_options: Vec<String>,
+    /// optional fields like the propagation tags `shared:X`, `master:X`,
+    /// `propagate_from:X` and `unbindable`
+    pub propagation: Vec<String>,
+    /// the filesystem type
+    pub filesystem: String,
+    /// the mount source, e.g. the device path
+    pub source: String,
+    /// per-superblock options
+    pub super_options: Vec<String>,
+}
+
+impl MountInfo {
+    fn parse(line: &str) -> Option<MountInfo> {
+        let mut fields = line.split_whitespace();
+        let mount_id = fields.next()?.parse().ok()?;
+        let parent_id = fields.next()?.parse().ok()?;
+        let dev = fields.next()?.parse().ok()?;
+        let root = decode_octal_escapes(fields.next()?);
+        let mountpoint = decode_octal_escapes(fields.next()?);
+        let mount_options = split_options(fields.next()?);
+        // the optional fields are terminated by a single hyphen
+        let mut propagation = vec![];
+        for field in fields.by_ref() {
+            if field == "-" {
+                break;
+            }
+            propagation.push(field.to_owned());
+        }
+        let filesystem = fields.next()?.to_owned();
+        let source = decode_octal_escapes(fields.next()?);
+        let super_options = fields.next().map(split_options).unwrap_or_default();
+        Some(MountInfo {
+            mount_id,
+            parent_id,
+            dev,
+            root,
+            mountpoint,
+            mount_options,
+            propagation,
+            filesystem,
+            source,
+            super_options,
+        })
+    }
+}
+
+// the kernel escapes space, tab, newline and backslash as a backslash
+// followed by three octal digits (e.g. `\040` for a space)
+fn decode_octal_escapes(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'\\' {
+            let code = value
+                .get(index + 1..index + 4)
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+            if let Some(code) = code {
+                decoded.push(code);
+                index += 4;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn split_options(options: &str) -> Vec<String> {
+    options.split(',').map(|option| option.to_owned()).collect()
+}
 
 /// Informations about the mount point of a drives patition
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mount {
     /// the partitions linux device name
     pub device: String,
@@ -9,25 +114,97 @@ pub struct Mount {
     pub mountpoint: String,
     /// the filesystem used to mount the partition
     pub filesystem: String,
+    /// the directory within the filesystem that is mounted (see [`MountInfo::root`])
+    pub root: String,
+    /// major:minor of the mounted device
+    pub dev: DeviceNumber,
+    /// unique id of the mount
+    pub mount_id: u32,
+    /// the options the filesystem is mounted with
+    pub options: MountOptions,
 }
 
+impl From<&MountInfo> for Mount {
+    fn from(info: &MountInfo) -> Self {
+        Mount {
+            device: info.source.clone(),
+            mountpoint: info.mountpoint.clone(),
+            filesystem: info.filesystem.clone(),
+            root: info.root.clone(),
+            dev: info.dev,
+            mount_id: info.mount_id,
+            options: MountOptions::from_mountinfo(&info.mount_options, &info.super_options),
+        }
+    }
+}
+
+impl Mount {
+    /// returns the usage statistics (size, free space, inodes) of the mounted
+    /// filesystem by calling statvfs on the mount point
+    pub fn usage(&self) -> Result<FilesystemUsage, DrivesError> {
+        usage::statvfs(&self.mountpoint)
+    }
+}
+
+/// A mount together with the block device its source resolves to
+///
+/// Mount sources are often symlinks (`/dev/mapper/*`, `/dev/disk/by-uuid/*`) and
+/// some filesystems (e.g. btrfs) report an anonymous major:minor in mountinfo,
+/// so both the resolved device name and the device number of the node are kept.
+#[derive(Debug)]
+pub struct ResolvedMount {
+    pub mount: Mount,
+    /// name of the device node the source points to (e.g. `dm-0` for `/dev/mapper/vg-root`)
+    pub device_name: Option<String>,
+    /// major:minor of the device node the source points to
+    pub device_number: Option<DeviceNumber>,
+}
+
+impl ResolvedMount {
+    fn resolve(mount: Mount, dev_root: &Path) -> ResolvedMount {
+        let node_path = mount
+            .device
+            .strip_prefix("/dev/")
+            .map(|relative| dev_root.join(relative))
+            .and_then(|path| fs::canonicalize(path).ok());
+        let device_name = node_path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .and_then(|name| name.to_str())
+            .map(|name| name.to_owned());
+        let device_number = node_path
+            .and_then(|path| fs::metadata(path).ok())
+            .filter(|metadata| metadata.file_type().is_block_device())
+            .map(|metadata| DeviceNumber::from_dev_t(metadata.rdev()));
+        ResolvedMount {
+            mount,
+            device_name,
+            device_number,
+        }
+    }
+
+    /// checks if the mount belongs to the block device with the given name and
+    /// major:minor - exact matches only, so `sda1` does not match `/dev/sda10`
+    pub fn is_mount_of(&self, name: &str, dev: Option<DeviceNumber>) -> bool {
+        if dev.is_some() && (Some(self.mount.dev) == dev || self.device_number == dev) {
+            return true;
+        }
+        self.device_name.as_deref() == Some(name)
+    }
+}
+
+#[derive(Debug)]
 pub struct Mounts {
     mount_file_path: String,
 }
 
 impl Mounts {
-    pub fn read_mountpoints(&self) -> Result<Vec<Mount>, DrivesError> {
+    pub fn read_mountinfo(&self) -> Result<Vec<MountInfo>, DrivesError> {
         let mut mounts = vec![];
         if let Ok(lines) = read_lines(&self.mount_file_path) {
             lines.for_each(|line| {
-                if let Ok(mount) = line {
-                    let numerates = mount.split_whitespace().enumerate();
-                    let item: Vec<&str> = numerates.map(|(_, e)| e).collect();
-                    mounts.push(Mount {
-                        device: item[0].to_owned(),
-                        mountpoint: item[1].to_owned(),
-                        filesystem: item[2].to_owned(),
-                    });
+                if let Some(mount) = line.ok().as_deref().and_then(MountInfo::parse) {
+                    mounts.push(mount);
                 }
             });
         } else {
@@ -37,9 +214,29 @@ impl Mounts {
         Ok(mounts)
     }
 
-    pub fn new() -> Mounts {
+    pub fn read_mountpoints(&self) -> Result<Vec<Mount>, DrivesError> {
+        Ok(self.read_mountinfo()?.iter().map(Mount::from).collect())
+    }
+
+    /// reads the mounts and resolves their sources against the given dev directory
+    pub fn read_resolved_mounts(&self, dev_root: &Path) -> Result<Vec<ResolvedMount>, DrivesError> {
+        Ok(self
+            .read_mountpoints()?
+            .into_iter()
+            .map(|mount| ResolvedMount::resolve(mount, dev_root))
+            .collect())
+    }
+
+    /// the path of the mountinfo file the mounts are read from
+    pub fn mount_file_path(&self) -> &str {
+        &self.mount_file_path
+    }
+
+    pub fn new(procfs_root: &Path) -> Mounts {
         Mounts {
-            mount_file_path: "/proc/mounts".to_owned(),
+            mount_file_path: crate::fs_wrap::path_to_string(
+                &procfs_root.join("self").join("mountinfo"),
+            ),
         }
     }
 }
@@ -57,8 +254,8 @@ mod tests {
     fn test_read_mounts() {
         let mut test_file = NamedTempFile::new().unwrap();
         let content = concat!(
-            "/dev/sda1 / ext4 rw,relatime 0 0\n",
-            "/dev/sda2 /home ext4 rw,relatime 0 0"
+            "26 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw\n",
+            "27 26 8:2 / /home rw,relatime shared:2 - ext4 /dev/sda2 rw"
         );
 
         test_file.write_all(content.as_bytes()).unwrap();
@@ -74,6 +271,7 @@ mod tests {
         assert_eq!(sda1.device, "/dev/sda1");
         assert_eq!(sda1.filesystem, "ext4");
         assert_eq!(sda1.mountpoint, "/");
+        assert_eq!(sda1.dev, DeviceNumber::new(8, 1));
 
         let sda2 = result
             .iter()
@@ -82,5 +280,127 @@ mod tests {
         assert_eq!(sda2.device, "/dev/sda2");
         assert_eq!(sda2.filesystem, "ext4");
         assert_eq!(sda2.mountpoint, "/home");
+        assert_eq!(sda2.mount_id, 27);
+    }
+
+    #[test]
+    fn test_read_mountinfo() {
+        let mut test_file = NamedTempFile::new().unwrap();
+        let content = concat!(
+            "36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue\n",
+            "80 26 0:33 /@home /home rw,relatime shared:5 master:2 - btrfs /dev/nvme0n1p2 rw,ssd,subvol=/@home\n",
+            "90 26 0:40 / /run/user rw,nosuid,nodev - tmpfs tmpfs rw,size=1000k\n",
+            "not a mountinfo line\n"
+        );
+        test_file.write_all(content.as_bytes()).unwrap();
+
+        let mounts = Mounts {
+            mount_file_path: test_file.path().to_str().unwrap().to_owned(),
+        };
+        let result = mounts.read_mountinfo().unwrap();
+        assert_eq!(3, result.len());
+
+        let bind = &result[0];
+        assert_eq!(36, bind.mount_id);
+        assert_eq!(35, bind.parent_id);
+        assert_eq!(DeviceNumber::new(98, 0), bind.dev);
+        assert_eq!("/mnt1", bind.root);
+        assert_eq!("/mnt2", bind.mountpoint);
+        assert_eq!(vec!["rw", "noatime"], bind.mount_options);
+        assert_eq!(vec!["master:1"], bind.propagation);
+        assert_eq!("ext3", bind.filesystem);
+        assert_eq!("/dev/root", bind.source);
+        assert_eq!(vec!["rw", "errors=continue"], bind.super_options);
+
+        let subvolume = &result[1];
+        assert_eq!("/@home", subvolume.root);
+        assert_eq!(vec!["shared:5", "master:2"], subvolume.propagation);
+        assert_eq!("btrfs", subvolume.filesystem);
+        assert_eq!("/dev/nvme0n1p2", subvolume.source);
+
+        let tmpfs = &result[2];
+        assert!(tmpfs.propagation.is_empty());
+        assert_eq!("tmpfs", tmpfs.source);
+    }
+
+    #[test]
+    fn test_decode_octal_escapes() {
+        assert_eq!(
+            "/media/user/My Stick",
+            decode_octal_escapes(r"/media/user/My\040Stick")
+        );
+        assert_eq!("a\tb\nc\\d", decode_octal_escapes(r"a\011b\012c\134d"));
+        assert_eq!(r"no\escape\09", decode_octal_escapes(r"no\escape\09"));
+        assert_eq!(r"end\04", decode_octal_escapes(r"end\04"));
+    }
+
+    #[test]
+    fn test_escaped_mountpoint_and_options() {
+        let mut test_file = NamedTempFile::new().unwrap();
+        let content = r"50 26 8:17 / /media/user/My\040Stick rw,nosuid,nodev,relatime shared:3 - vfat /dev/sdb1 rw,uid=1000,umask=077";
+        test_file.write_all(content.as_bytes()).unwrap();
+
+        let mounts = Mounts {
+            mount_file_path: test_file.path().to_str().unwrap().to_owned(),
+        };
+        let result = mounts.read_mountpoints().unwrap();
+        let stick = &result[0];
+        assert_eq!("/media/user/My Stick", stick.mountpoint);
+        assert!(!stick.options.read_only);
+        assert!(stick.options.nosuid);
+        assert!(stick.options.nodev);
+        assert!(stick.options.relatime);
+        assert_eq!(Some("1000"), stick.options.get("uid"));
+        assert_eq!(Some("077"), stick.options.get("umask"));
+    }
+
+    #[test]
+    fn test_resolve_mounts() {
+        let dev_dir = tempfile::tempdir().unwrap();
+        let dev_path = dev_dir.path();
+        fs::write(dev_path.join("sda1"), "").unwrap();
+        fs::write(dev_path.join("sda10"), "").unwrap();
+        fs::write(dev_path.join("dm-0"), "").unwrap();
+        fs::create_dir_all(dev_path.join("disk").join("by-uuid")).unwrap();
+        std::os::unix::fs::symlink("../../sda1", dev_path.join("disk/by-uuid/1234")).unwrap();
+        fs::create_dir(dev_path.join("mapper")).unwrap();
+        std::os::unix::fs::symlink("../dm-0", dev_path.join("mapper/vg0-root")).unwrap();
+
+        let mut test_file = NamedTempFile::new().unwrap();
+        let content = concat!(
+            "20 1 0:30 / / rw - btrfs /dev/disk/by-uuid/1234 rw\n",
+            "21 20 8:10 / /mnt/ten rw - ext4 /dev/sda10 rw\n",
+            "22 20 0:31 / /var rw - btrfs /dev/mapper/vg0-root rw\n",
+            "23 20 8:2 / /mnt/root rw - ext4 /dev/root rw\n",
+            "24 20 0:40 / /tmp rw - tmpfs tmpfs rw\n",
+        );
+        test_file.write_all(content.as_bytes()).unwrap();
+        let mounts = Mounts {
+            mount_file_path: test_file.path().to_str().unwrap().to_owned(),
+        };
+
+        let result = mounts.read_resolved_mounts(dev_path).unwrap();
+        let is_mount_of = |index: usize, name: &str, dev: DeviceNumber| {
+            result[index].is_mount_of(name, Some(dev))
+        };
+        assert!(is_mount_of(0, "sda1", DeviceNumber::new(8, 1)));
+        assert!(!is_mount_of(0, "sda10", DeviceNumber::new(8, 10)));
+        assert!(is_mount_of(1, "sda10", DeviceNumber::new(8, 10)));
+        assert!(!is_mount_of(1, "sda1", DeviceNumber::new(8, 1)));
+        assert!(is_mount_of(2, "dm-0", DeviceNumber::new(253, 0)));
+        assert!(is_mount_of(3, "sda2", DeviceNumber::new(8, 2)));
+        assert!(!is_mount_of(4, "sda1", DeviceNumber::new(8, 1)));
+        assert_eq!(None, result[4].device_name);
+    }
+
+    #[test]
+    fn test_read_mounts_missing_file() {
+        let mounts = Mounts {
+            mount_file_path: "/does/not/exist".to_owned(),
+        };
+        assert!(matches!(
+            mounts.read_mountinfo(),
+            Err(DrivesError::ReadingMountsFailed)
+        ));
     }
 }
@@ -0,0 +1,359 @@
+use std::{fs, path::Path};
+
+use crate::fs_wrap;
+
+/// Details of an NVMe namespace and the controller it is attached to
+///
+/// For a multipath namespace (the visible head device of an NVMe subsystem)
+/// model, serial and firmware are read from the subsystem, transport and
+/// state from the first live path.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NvmeInfo {
+    /// the controller (`nvme0`) or for a multipath namespace the subsystem (`nvme-subsys0`)
+    pub controller: String,
+    /// the model of the controller
+    pub model: Option<String>,
+    /// the serial of the controller
+    pub serial: Option<String>,
+    /// the firmware revision of the controller
+    pub firmware_revision: Option<String>,
+    /// how the controller is attached: `pcie`, `tcp`, `rdma`, `fc` or `loop`
+    pub transport: Option<String>,
+    /// the state of the controller, e.g. `live`, `resetting` or `connecting`
+    pub state: Option<String>,
+    /// the namespace ID
+    pub namespace_id: Option<u32>,
+    /// the IEEE extended unique identifier, None if not reported
+    pub eui64: Option<String>,
+    /// the namespace globally unique identifier, None if not reported
+    pub nguid: Option<String>,
+    /// the namespace UUID, None if not reported
+    pub uuid: Option<String>,
+    /// the world wide identifier the kernel derives from the IDs above
+    pub wwid: Option<String>,
+    /// the NVMe qualified name of the subsystem
+    pub subsystem_nqn: Option<String>,
+    /// the paths of a multipath namespace (the hidden `nvmeXcYnZ` devices),
+    /// empty without multipath
+    pub paths: Vec<NvmePath>,
+}
+
+/// One path (controller) of a multipath NVMe namespace
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NvmePath {
+    /// the name of the hidden path device, e.g. `nvme0c1n1`
+    pub name: String,
+    /// the controller of the path, e.g. `nvme1`
+    pub controller: String,
+    /// how the controller is attached: `pcie`, `tcp`, `rdma`, `fc` or `loop`
+    pub transport: Option<String>,
+    /// the state of the controller
+    pub state: Option<String>,
+    /// the asymmetric namespace access state, e.g. `optimized` or `inaccessible`
+    pub ana_state: Option<String>,
+}
+
+// skips the leading number, e.g. "0c1n1" to "c1n1", None without a number
+fn skip_number(value: &str) -> Option<&str> {
+    let rest = value.trim_start_matches(|c: char| c.is_ascii_digit());
+    (rest.len() < value.len()).then_some(rest)
+}
+
+// parses nvme<instance>[c<controller>]n<namespace>, returns whether the
+// name has the controller of a multipath path (the instance is the subsystem then)
+fn parse_name(name: &str) -> Option<bool> {
+    let rest = skip_number(name.strip_prefix("nvme")?)?;
+    let (is_path, rest) = match rest.strip_prefix('c') {
+        Some(rest) => (true, skip_number(rest)?),
+        None => (false, rest),
+    };
+    skip_number(rest.strip_prefix('n')?)?
+        .is_empty()
+        .then_some(is_path)
+}
+
+/// whether the name is a namespace block device like `nvme0n1`
+pub(crate) fn is_namespace(name: &str) -> bool {
+    parse_name(name) == Some(false)
+}
+
+/// whether the name is a hidden path of a multipath namespace like `nvme0c1n1`
+pub(crate) fn is_multipath_path(name: &str) -> bool {
+    parse_name(name) == Some(true)
+}
+
+fn read_optional(dir: &Path, file: &str) -> Option<String> {
+    fs_wrap::read_file_to_string(&dir.join(file))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+// a namespace without an identifier reports zeros
+fn read_id(dir: &Path, file: &str) -> Option<String> {
+    read_optional(dir, file)
+        .map(|id| id.replace(' ', ""))
+        .filter(|id| id.chars().any(|c| c != '0' && c != '-'))
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn read_path(path_dir: &Path) -> Option<NvmePath> {
+    let path_dir = fs::canonicalize(path_dir).ok()?;
+    let controller_dir = path_dir.parent()?;
+    Some(NvmePath {
+        name: dir_name(&path_dir),
+        controller: dir_name(controller_dir),
+        transport: read_optional(controller_dir, "transport"),
+        state: read_optional(controller_dir, "state"),
+        ana_state: read_optional(&path_dir, "ana_state"),
+    })
+}
+
+fn read_paths(namespace_dir: &Path) -> Vec<NvmePath> {
+    let mut paths: Vec<NvmePath> = fs::read_dir(namespace_dir.join("multipath"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| read_path(&entry.path()))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort_by(|a, b| a.name.cmp(&b.name));
+    paths
+}
+
+/// Reads the namespace attributes and those of its controller (the parent
+/// directory in sysfs), for a multipath head the parent is the subsystem
+pub(crate) fn read_nvme_info(namespace_dir: &Path) -> Option<NvmeInfo> {
+    let namespace_dir = fs::canonicalize(namespace_dir).ok()?;
+    let controller_dir = namespace_dir.parent()?;
+    let paths = read_paths(&namespace_dir);
+    let active_path = paths
+        .iter()
+        .find(|path| path.state.as_deref() == Some("live"))
+        .or(paths.first());
+    let (transport, state) = match active_path {
+        Some(path) => (path.transport.clone(), path.state.clone()),
+        None => (
+            read_optional(controller_dir, "transport"),
+            read_optional(controller_dir, "state"),
+        ),
+    };
+    Some(NvmeInfo {
+        controller: dir_name(controller_dir),
+        model: read_optional(controller_dir, "model"),
+        serial: read_optional(controller_dir, "serial"),
+        firmware_revision: read_optional(controller_dir, "firmware_rev"),
+        transport,
+        state,
+        namespace_id: read_optional(&namespace_dir, "nsid").and_then(|nsid| nsid.parse().ok()),
+        eui64: read_id(&namespace_dir, "eui"),
+        nguid: read_id(&namespace_dir, "nguid"),
+        uuid: read_id(&namespace_dir, "uuid"),
+        wwid: read_optional(&namespace_dir, "wwid"),
+        subsystem_nqn: read_optional(controller_dir, "subsysnqn"),
+        paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use tempfile::tempdir;
+
+    use crate::{DeviceKind, DrivesBuilder, Transport};
+
+    const CONTROLLER: &str = "devices/pci0000:00/0000:00:1d.0/0000:3d:00.0/nvme/nvme0";
+    const SUBSYSTEM: &str = "devices/virtual/nvme-subsystem/nvme-subsys1";
+    const FABRICS: &str = "devices/virtual/nvme-fabrics/ctl";
+
+    fn write_files(dir: &Path, files: &[(&str, &str)]) {
+        fs::create_dir_all(dir).unwrap();
+        for (file, content) in files {
+            fs::write(dir.join(file), format!("{}\n", content)).unwrap();
+        }
+    }
+
+    fn link_block_device(sysfs_root: &Path, target: &str, name: &str) {
+        fs::create_dir_all(sysfs_root.join("block")).unwrap();
+        std::os::unix::fs::symlink(
+            PathBuf::from("..").join(target).join(name),
+            sysfs_root.join("block").join(name),
+        )
+        .unwrap();
+    }
+
+    // a local pcie controller with one namespace and a subsystem reachable by
+    // two tcp controllers with the multipath namespace nvme1n1
+    fn create_fake_sysfs(sysfs_root: &Path) {
+        let controller = sysfs_root.join(CONTROLLER);
+        write_files(
+            &controller,
+            &[
+                ("model", "Samsung SSD 980 PRO 1TB"),
+                ("serial", "S5GXNF0R123456"),
+                ("firmware_rev", "5B2QGXA7"),
+                ("transport", "pcie"),
+                ("state", "live"),
+                (
+                    "subsysnqn",
+                    "nqn.1994-11.com.samsung:nvme:980PRO:M.2:S5GXNF0R123456",
+                ),
+            ],
+        );
+        write_files(
+            &controller.join("nvme0n1"),
+            &[
+                ("nsid", "1"),
+                ("wwid", "eui.002538b121b0c4a1"),
+                ("eui", "00 25 38 b1 21 b0 c4 a1"),
+                ("nguid", "00000000-0000-0000-0000-000000000000"),
+                ("uuid", "00000000-0000-0000-0000-000000000000"),
+                ("removable", "0"),
+                ("size", "1953525168"),
+            ],
+        );
+        link_block_device(sysfs_root, CONTROLLER, "nvme0n1");
+
+        let subsystem = sysfs_root.join(SUBSYSTEM);
+        write_files(
+            &subsystem,
+            &[
+                ("model", "Linux"),
+                ("serial", "8f3a1c2b4d5e6f70"),
+                ("firmware_rev", "6.8.0"),
+                ("subsysnqn", "nqn.2024-01.org.example:storage"),
+            ],
+        );
+        let head = subsystem.join("nvme1n1");
+        write_files(
+            &head,
+            &[
+                ("nsid", "1"),
+                ("wwid", "uuid.6b2c8a9e-1f3d-4c5b-9a7e-2d4f6a8b0c1e"),
+                ("uuid", "6b2c8a9e-1f3d-4c5b-9a7e-2d4f6a8b0c1e"),
+                ("removable", "0"),
+                ("size", "209715200"),
+            ],
+        );
+        link_block_device(sysfs_root, SUBSYSTEM, "nvme1n1");
+        fs::create_dir(head.join("multipath")).unwrap();
+        // paths are named nvme<subsystem>c<controller>n<namespace>
+        for (controller, path, state, ana_state) in [
+            ("nvme1", "nvme1c1n1", "live", "optimized"),
+            ("nvme2", "nvme1c2n1", "connecting", "inaccessible"),
+        ] {
+            write_files(
+                &sysfs_root.join(FABRICS).join(controller),
+                &[("transport", "tcp"), ("state", state)],
+            );
+            write_files(
+                &sysfs_root.join(FABRICS).join(controller).join(path),
+                &[
+                    ("ana_state", ana_state),
+                    ("removable", "0"),
+                    ("size", "209715200"),
+                ],
+            );
+            std::os::unix::fs::symlink(
+                PathBuf::from("../../../../nvme-fabrics/ctl")
+                    .join(controller)
+                    .join(path),
+                head.join("multipath").join(path),
+            )
+            .unwrap();
+            link_block_device(sysfs_root, &format!("{}/{}", FABRICS, controller), path);
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        assert!(is_namespace("nvme0n1"));
+        assert!(is_namespace("nvme12n3"));
+        assert!(!is_namespace("nvme0n1p1"));
+        assert!(!is_namespace("nvme0c1n1"));
+        assert!(!is_namespace("sda"));
+        assert!(is_multipath_path("nvme0c1n1"));
+        assert!(!is_multipath_path("nvme0n1"));
+        assert!(!is_multipath_path("nvme0c1n"));
+    }
+
+    #[test]
+    fn test_read_nvme_info() {
+        let sysfs_root = tempdir().unwrap();
+        create_fake_sysfs(sysfs_root.path());
+
+        let nvme = read_nvme_info(&sysfs_root.path().join("block").join("nvme0n1")).unwrap();
+        assert_eq!("nvme0", nvme.controller);
+        assert_eq!(Some("Samsung SSD 980 PRO 1TB".to_string()), nvme.model);
+        assert_eq!(Some("S5GXNF0R123456".to_string()), nvme.serial);
+        assert_eq!(Some("5B2QGXA7".to_string()), nvme.firmware_revision);
+        assert_eq!(Some("pcie".to_string()), nvme.transport);
+        assert_eq!(Some("live".to_string()), nvme.state);
+        assert_eq!(Some(1), nvme.namespace_id);
+        assert_eq!(Some("002538b121b0c4a1".to_string()), nvme.eui64);
+        assert_eq!(None, nvme.nguid);
+        assert_eq!(None, nvme.uuid);
+        assert_eq!(Some("eui.002538b121b0c4a1".to_string()), nvme.wwid);
+        assert!(nvme.paths.is_empty());
+    }
+
+    #[test]
+    fn test_read_multipath_nvme_info() {
+        let sysfs_root = tempdir().unwrap();
+        create_fake_sysfs(sysfs_root.path());
+
+        let nvme = read_nvme_info(&sysfs_root.path().join("block").join("nvme1n1")).unwrap();
+        assert_eq!("nvme-subsys1", nvme.controller);
+        assert_eq!(Some("Linux".to_string()), nvme.model);
+        assert_eq!(Some("tcp".to_string()), nvme.transport);
+        assert_eq!(Some("live".to_string()), nvme.state);
+        assert_eq!(
+            Some("nqn.2024-01.org.example:storage".to_string()),
+            nvme.subsystem_nqn
+        );
+        assert_eq!(
+            Some("6b2c8a9e-1f3d-4c5b-9a7e-2d4f6a8b0c1e".to_string()),
+            nvme.uuid
+        );
+        assert_eq!(2, nvme.paths.len());
+        assert_eq!("nvme1c1n1", nvme.paths[0].name);
+        assert_eq!("nvme1", nvme.paths[0].controller);
+        assert_eq!(Some("optimized".to_string()), nvme.paths[0].ana_state);
+        assert_eq!("nvme1c2n1", nvme.paths[1].name);
+        assert_eq!("nvme2", nvme.paths[1].controller);
+        assert_eq!(Some("connecting".to_string()), nvme.paths[1].state);
+    }
+
+    #[test]
+    fn test_paths_grouped_under_head() {
+        let sysfs_root = tempdir().unwrap();
+        create_fake_sysfs(sysfs_root.path());
+
+        // virtual devices are included, the paths are hidden because they belong to the head
+        let mut devices = DrivesBuilder::new()
+            .sysfs_root(sysfs_root.path())
+            .read_udev(false)
+            .build()
+            .get_devices()
+            .unwrap();
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        let names: Vec<&str> = devices.iter().map(|device| device.name.as_str()).collect();
+        assert_eq!(vec!["nvme0n1", "nvme1n1"], names);
+
+        let head = &devices[1];
+        assert_eq!(DeviceKind::Disk, head.kind);
+        assert_eq!(Transport::Nvme, head.transport);
+        assert_eq!(Some("Linux".to_string()), head.model);
+        assert_eq!(2, head.nvme.as_ref().unwrap().paths.len());
+    }
+}
@@ -0,0 +1,780 @@
+//!
+//! Detection of filesystems by reading their superblocks, similar to blkid.
+//!
+//! Works on any `Read + Seek` source, like a partition device node, a disk image
+//! or an in-memory buffer. No udev, mounts or elevated privileges (besides read
+//! access to the source) are needed.
+//!
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{error::DrivesError, fs_wrap::path_to_string};
+
+/// The filesystems (and other on-disk formats) that can be detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FilesystemType {
+    Ext2,
+    Ext3,
+    Ext4,
+    Fat12,
+    Fat16,
+    Fat32,
+    Exfat,
+    Ntfs,
+    Btrfs,
+    Xfs,
+    Iso9660,
+    Swap,
+    Luks1,
+    Luks2,
+}
+
+impl FilesystemType {
+    /// the type name as used by blkid, udev (ID_FS_TYPE) and mount
+    pub fn name(&self) -> &str {
+        match *self {
+            FilesystemType::Ext2 => "ext2",
+            FilesystemType::Ext3 => "ext3",
+            FilesystemType::Ext4 => "ext4",
+            FilesystemType::Fat12 | FilesystemType::Fat16 | FilesystemType::Fat32 => "vfat",
+            FilesystemType::Exfat => "exfat",
+            FilesystemType::Ntfs => "ntfs",
+            FilesystemType::Btrfs => "btrfs",
+            FilesystemType::Xfs => "xfs",
+            FilesystemType::Iso9660 => "iso9660",
+            FilesystemType::Swap => "swap",
+            FilesystemType::Luks1 | FilesystemType::Luks2 => "crypto_LUKS",
+        }
+    }
+}
+
+impl fmt::Display for FilesystemType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The result of probing a superblock
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilesystemInfo {
+    /// the detected type
+    pub fs_type: FilesystemType,
+    /// the label, if one is set
+    pub label: Option<String>,
+    /// the UUID (or serial number for FAT, exFAT and NTFS) formatted like blkid does
+    pub uuid: Option<String>,
+}
+
+// enough to cover all superblock locations checked below (btrfs at 64 KiB)
+const PROBE_SIZE: usize = 0x10000 + 0x1000;
+
+// little helper to access a buffer that may be shorter than expected (small
+// images or partitions), every access outside of the buffer returns None
+struct Block<'a>(&'a [u8]);
+
+impl Block<'_> {
+    fn bytes(&self, offset: usize, len: usize) -> Option<&[u8]> {
+        self.0.get(offset..offset.checked_add(len)?)
+    }
+
+    fn u8(&self, offset: usize) -> Option<u8> {
+        self.0.get(offset).copied()
+    }
+
+    fn le_u16(&self, offset: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(self.bytes(offset, 2)?.try_into().ok()?))
+    }
+
+    fn be_u16(&self, offset: usize) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(offset, 2)?.try_into().ok()?))
+    }
+
+    fn le_u32(&self, offset: usize) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(offset, 4)?.try_into().ok()?))
+    }
+
+    fn le_u64(&self, offset: usize) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(offset, 8)?.try_into().ok()?))
+    }
+
+    fn has_magic(&self, offset: usize, magic: &[u8]) -> bool {
+        self.bytes(offset, magic.len()) == Some(magic)
+    }
+
+    // a nul or space padded string
+    fn string(&self, offset: usize, len: usize) -> Option<String> {
+        let bytes = self.bytes(offset, len)?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        let value = String::from_utf8_lossy(&bytes[..end]).trim_end().to_owned();
+        (!value.is_empty()).then_some(value)
+    }
+
+    fn uuid(&self, offset: usize) -> Option<String> {
+        let bytes = self.bytes(offset, 16)?;
+        if bytes.iter().all(|&b| b == 0) {
+            return None;
+        }
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        Some(format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        ))
+    }
+}
+
+fn utf16le_string(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn serial_uuid(serial: u32) -> String {
+    format!("{:04X}-{:04X}", serial >> 16, serial & 0xffff)
+}
+
+// reads up to len bytes at offset, less if the source ends before
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buffer = Vec::with_capacity(len);
+    reader.take(len as u64).read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn probe_luks(block: &Block) -> Option<FilesystemInfo> {
+    if !block.has_magic(0, b"LUKS\xba\xbe") {
+        return None;
+    }
+    let (fs_type, label) = match block.be_u16(6)? {
+        1 => (FilesystemType::Luks1, None),
+        2 => (FilesystemType::Luks2, block.string(24, 48)),
+        _ => return None,
+    };
+    Some(FilesystemInfo {
+        fs_type,
+        label,
+        uuid: block.string(168, 40),
+    })
+}
+
+fn probe_xfs(block: &Block) -> Option<FilesystemInfo> {
+    if !block.has_magic(0, b"XFSB") {
+        return None;
+    }
+    Some(FilesystemInfo {
+        fs_type: FilesystemType::Xfs,
+        label: block.string(108, 12),
+        uuid: block.uuid(32),
+    })
+}
+
+fn probe_btrfs(block: &Block) -> Option<FilesystemInfo> {
+    const SUPERBLOCK: usize = 0x10000;
+    if !block.has_magic(SUPERBLOCK + 0x40, b"_BHRfS_M") {
+        return None;
+    }
+    Some(FilesystemInfo {
+        fs_type: FilesystemType::Btrfs,
+        label: block.string(SUPERBLOCK + 0x12b, 256),
+        uuid: block.uuid(SUPERBLOCK + 0x20),
+    })
+}
+
+fn probe_ext(block: &Block) -> Option<FilesystemInfo> {
+    const SUPERBLOCK: usize = 0x400;
+    const COMPAT_HAS_JOURNAL: u32 = 0x0004;
+    // features beyond sparse_super, large_file and btree_dir
+    const RO_COMPAT_EXT4: u32 = !0x0007;
+    // extents, 64bit, flex_bg and everything newer
+    const INCOMPAT_EXT4: u32 = !0x001f;
+    if block.le_u16(SUPERBLOCK + 0x38)? != 0xef53 {
+        return None;
+    }
+    let compat = block.le_u32(SUPERBLOCK + 0x5c)?;
+    let incompat = block.le_u32(SUPERBLOCK + 0x60)?;
+    let ro_compat = block.le_u32(SUPERBLOCK + 0x64)?;
+    let fs_type = if incompat & INCOMPAT_EXT4 != 0 || ro_compat & RO_COMPAT_EXT4 != 0 {
+        FilesystemType::Ext4
+    } else if compat & COMPAT_HAS_JOURNAL != 0 {
+        FilesystemType::Ext3
+    } else {
+        FilesystemType::Ext2
+    };
+    Some(FilesystemInfo {
+        fs_type,
+        label: block.string(SUPERBLOCK + 0x78, 16),
+        uuid: block.uuid(SUPERBLOCK + 0x68),
+    })
+}
+
+fn probe_iso9660(block: &Block) -> Option<FilesystemInfo> {
+    // the primary volume descriptor is the first one at sector 16 (2048 bytes per sector)
+    const DESCRIPTOR: usize = 0x8000;
+    if block.u8(DESCRIPTOR)? != 1 || !block.has_magic(DESCRIPTOR + 1, b"CD001") {
+        return None;
+    }
+    // like blkid the creation date (YYYYMMDDHHMMSScc) is used as UUID
+    let uuid = block
+        .bytes(DESCRIPTOR + 813, 16)
+        .filter(|date| date.iter().all(|b| b.is_ascii_digit()) && date != b"0000000000000000")
+        .map(|date| {
+            let date = String::from_utf8_lossy(date);
+            format!(
+                "{}-{}-{}-{}-{}-{}-{}",
+                &date[0..4],
+                &date[4..6],
+                &date[6..8],
+                &date[8..10],
+                &date[10..12],
+                &date[12..14],
+                &date[14..16]
+            )
+        });
+    Some(FilesystemInfo {
+        fs_type: FilesystemType::Iso9660,
+        label: block.string(DESCRIPTOR + 40, 32),
+        uuid,
+    })
+}
+
+fn probe_swap(block: &Block) -> Option<FilesystemInfo> {
+    // the signature is at the end of the first page, check the common page sizes
+    let has_signature = [0x1000, 0x2000, 0x4000, 0x10000]
+        .into_iter()
+        .any(|page_size| block.has_magic(page_size - 10, b"SWAPSPACE2"));
+    if !has_signature {
+        return None;
+    }
+    Some(FilesystemInfo {
+        fs_type: FilesystemType::Swap,
+        label: block.string(0x400 + 28, 16),
+        uuid: block.uuid(0x400 + 12),
+    })
+}
+
+fn probe_exfat<R: Read + Seek>(block: &Block, reader: &mut R) -> Option<FilesystemInfo> {
+    if !block.has_magic(3, b"EXFAT   ") {
+        return None;
+    }
+    let uuid = block.le_u32(100).map(serial_uuid);
+    // the label is stored as an entry of the root directory
+    let label = (|| {
+        let bytes_per_sector_shift = u32::from(block.u8(108)?);
+        let sectors_per_cluster_shift = u32::from(block.u8(109)?);
+        let cluster_heap_offset = u64::from(block.le_u32(88)?);
+        let root_cluster = u64::from(block.le_u32(96)?);
+        let cluster_size =
+            1usize.checked_shl(bytes_per_sector_shift + sectors_per_cluster_shift)?;
+        let root_offset = cluster_heap_offset
+            .checked_add(root_cluster.checked_sub(2)? << sectors_per_cluster_shift)?
+            .checked_shl(bytes_per_sector_shift)?;
+        let root_dir = read_at(reader, root_offset, cluster_size.min(PROBE_SIZE)).ok()?;
+        for entry in root_dir.chunks_exact(32) {
+            match entry[0] {
+                // end of directory
+                0x00 => break,
+                // volume label entry
+                0x83 => {
+                    let length = usize::from(entry[1]).min(11);
+                    return Some(utf16le_string(&entry[2..2 + length * 2]));
+                }
+                _ => {}
+            }
+        }
+        None
+    })();
+    Some(FilesystemInfo {
+        fs_type: FilesystemType::Exfat,
+        label: label.filter(|label| !label.is_empty()),
+        uuid,
+    })
+}
+
+fn probe_ntfs<R: Read + Seek>(block: &Block, reader: &mut R) -> Option<FilesystemInfo> {
+    if !block.has_magic(3, b"NTFS    ") {
+        return None;
+    }
+    let bytes_per_sector = usize::from(block.le_u16(0x0b)?);
+    if !(256..=4096).contains(&bytes_per_sector) || !bytes_per_sector.is_power_of_two() {
+        return None;
+    }
+    let cluster_size = bytes_per_sector.checked_mul(usize::from(block.u8(0x0d)?))?;
+    // a positive value counts clusters, a negative one is the shift of the size in bytes
+    let record_size = match block.u8(0x40)? as i8 {
+        size @ 1.. => cluster_size.checked_mul(size as usize)?,
+        shift => 1usize.checked_shl(u32::from(shift.unsigned_abs()))?,
+    };
+    if !(256..=65536).contains(&record_size) || !record_size.is_power_of_two() {
+        return None;
+    }
+    let uuid = block.le_u64(0x48).map(|serial| format!("{:016X}", serial));
+    let label = (|| {
+        let mft_offset = block.le_u64(0x30)?.checked_mul(cluster_size as u64)?;
+        // $Volume is the fourth record of the master file table
+        let volume_offset = mft_offset.checked_add((record_size as u64).checked_mul(3)?)?;
+        let mut record = read_at(reader, volume_offset, record_size).ok()?;
+        if record.len() != record_size || !record.starts_with(b"FILE") {
+            return None;
+        }
+        // restore the last two bytes of each sector from the update sequence array
+        let update_sequence_offset = usize::from(Block(&record).le_u16(0x04)?);
+        let update_sequence_count = usize::from(Block(&record).le_u16(0x06)?);
+        for index in 1..update_sequence_count {
+            let fixup = Block(&record)
+                .bytes(update_sequence_offset + index * 2, 2)?
+                .to_vec();
+            let sector_end = index * bytes_per_sector;
+            record
+                .get_mut(sector_end.checked_sub(2)?..sector_end)?
+                .copy_from_slice(&fixup);
+        }
+        let record = Block(&record);
+        let mut attribute_offset = usize::from(record.le_u16(0x14)?);
+        loop {
+            let attribute_type = record.le_u32(attribute_offset)?;
+            let attribute_length = record.le_u32(attribute_offset + 4)? as usize;
+            if attribute_type == 0xffff_ffff || attribute_length == 0 {
+                return None;
+            }
+            // resident $VOLUME_NAME attribute
+            if attribute_type == 0x60 && record.u8(attribute_offset + 8)? == 0 {
+                let value_length = record.le_u32(attribute_offset + 0x10)? as usize;
+                let value_offset = usize::from(record.le_u16(attribute_offset + 0x14)?);
+                let value = record.bytes(attribute_offset + value_offset, value_length)?;
+                return Some(utf16le_string(value));
+            }
+            attribute_offset += attribute_length;
+        }
+    })();
+    Some(FilesystemInfo {
+        fs_type: FilesystemType::Ntfs,
+        label: label.filter(|label| !label.is_empty()),
+        uuid,
+    })
+}
+
+fn probe_fat(block: &Block) -> Option<FilesystemInfo> {
+    if block.le_u16(510)? != 0xaa55 || ![0xeb, 0xe9].contains(&block.u8(0)?) {
+        return None;
+    }
+    let bytes_per_sector = u32::from(block.le_u16(0x0b)?);
+    let sectors_per_cluster = u32::from(block.u8(0x0d)?);
+    let reserved_sectors = u32::from(block.le_u16(0x0e)?);
+    let number_of_fats = u32::from(block.u8(0x10)?);
+    if ![512, 1024, 2048, 4096].contains(&bytes_per_sector)
+        || !sectors_per_cluster.is_power_of_two()
+        || reserved_sectors == 0
+        || !(1..=2).contains(&number_of_fats)
+    {
+        return None;
+    }
+    let root_entries = u32::from(block.le_u16(0x11)?);
+    let total_sectors = match block.le_u16(0x13)? {
+        0 => block.le_u32(0x20)?,
+        sectors => u32::from(sectors),
+    };
+    let (fat_size, extended_boot_record) = match block.le_u16(0x16)? {
+        0 => (block.le_u32(0x24)?, 0x40),
+        sectors => (u32::from(sectors), 0x24),
+    };
+    let root_dir_sectors = (root_entries * 32).div_ceil(bytes_per_sector);
+    // the sizes come from the disk, garbage must not overflow
+    let metadata_sectors = number_of_fats
+        .checked_mul(fat_size)?
+        .checked_add(reserved_sectors)?
+        .checked_add(root_dir_sectors)?;
+    let data_sectors = total_sectors.checked_sub(metadata_sectors)?;
+    let clusters = data_sectors / sectors_per_cluster;
+    let fs_type = if extended_boot_record == 0x40 {
+        FilesystemType::Fat32
+    } else if clusters < 4085 {
+        FilesystemType::Fat12
+    } else {
+        FilesystemType::Fat16
+    };
+    // the serial and label are only valid with an extended boot signature
+    let (label, uuid) = if [0x28, 0x29].contains(&block.u8(extended_boot_record + 2)?) {
+        let uuid = block.le_u32(extended_boot_record + 3).map(serial_uuid);
+        let label = block
+            .string(extended_boot_record + 7, 11)
+            .filter(|label| label != "NO NAME");
+        (label, uuid)
+    } else {
+        (None, None)
+    };
+    Some(FilesystemInfo {
+        fs_type,
+        label,
+        uuid,
+    })
+}
+
+/// Probes the superblock at the beginning of the reader, returns `None` if no
+/// known filesystem was detected
+pub fn probe<R: Read + Seek>(reader: &mut R) -> Result<Option<FilesystemInfo>, DrivesError> {
+    let buffer = read_at(reader, 0, PROBE_SIZE).map_err(|_err| DrivesError::ProbeFailed)?;
+    let block = Block(&buffer);
+    let info = probe_luks(&block)
+        .or_else(|| probe_xfs(&block))
+        .or_else(|| probe_btrfs(&block))
+        .or_else(|| probe_ext(&block))
+        .or_else(|| probe_iso9660(&block))
+        .or_else(|| probe_swap(&block))
+        .or_else(|| probe_exfat(&block, reader))
+        .or_else(|| probe_ntfs(&block, reader))
+        .or_else(|| probe_fat(&block));
+    Ok(info)
+}
+
+/// Probes the superblock of a device node (e.g. `/dev/sda1`) or an image file
+pub fn probe_path(path: &Path) -> Result<Option<FilesystemInfo>, DrivesError> {
+    let mut file = File::open(path).map_err(|_err| DrivesError::FileAccessError {
+        filename: path_to_string(path),
+    })?;
+    probe(&mut file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    // a zeroed image with the given bytes written at their offsets
+    fn build_image(size: usize, content: &[(usize, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut image = vec![0u8; size];
+        for (offset, bytes) in content {
+            image[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+        }
+        Cursor::new(image)
+    }
+
+    const UUID_BYTES: [u8; 16] = [
+        0x3c, 0xdd, 0x69, 0x97, 0x9b, 0x47, 0x46, 0xf1, 0xa1, 0x60, 0x49, 0x54, 0x69, 0x76, 0xc2,
+        0x4e,
+    ];
+    const UUID: &str = "3cdd6997-9b47-46f1-a160-49546976c24e";
+
+    fn utf16(value: &str) -> Vec<u8> {
+        value
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect()
+    }
+
+    fn ext_image(compat: u32, incompat: u32, ro_compat: u32) -> Cursor<Vec<u8>> {
+        build_image(
+            4096,
+            &[
+                (0x438, &0xef53u16.to_le_bytes()),
+                (0x45c, &compat.to_le_bytes()),
+                (0x460, &incompat.to_le_bytes()),
+                (0x464, &ro_compat.to_le_bytes()),
+                (0x468, &UUID_BYTES),
+                (0x478, b"rootfs"),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_probe_ext() {
+        let info = probe(&mut ext_image(0, 0, 0x3)).unwrap().unwrap();
+        assert_eq!(FilesystemType::Ext2, info.fs_type);
+        assert_eq!(Some("rootfs".to_string()), info.label);
+        assert_eq!(Some(UUID.to_string()), info.uuid);
+
+        let info = probe(&mut ext_image(0x4, 0x2, 0x3)).unwrap().unwrap();
+        assert_eq!(FilesystemType::Ext3, info.fs_type);
+
+        let info = probe(&mut ext_image(0x4, 0x2c2, 0x46b)).unwrap().unwrap();
+        assert_eq!(FilesystemType::Ext4, info.fs_type);
+        assert_eq!("ext4", info.fs_type.name());
+    }
+
+    fn fat_boot_sector(fat32: bool, total_sectors: u32) -> Vec<(usize, Vec<u8>)> {
+        let mut content = vec![
+            (0, vec![0xeb, 0x3c, 0x90]),
+            (3, b"mkfs.fat".to_vec()),
+            (0x0b, 512u16.to_le_bytes().to_vec()),
+            (0x0d, vec![4]),
+            (0x0e, if fat32 { 32u16 } else { 4 }.to_le_bytes().to_vec()),
+            (0x10, vec![2]),
+            (0x20, total_sectors.to_le_bytes().to_vec()),
+            (510, vec![0x55, 0xaa]),
+        ];
+        let ebr = if fat32 {
+            content.push((0x24, 1000u32.to_le_bytes().to_vec()));
+            0x40
+        } else {
+            content.push((0x11, 512u16.to_le_bytes().to_vec()));
+            content.push((0x16, 32u16.to_le_bytes().to_vec()));
+            0x24
+        };
+        content.push((ebr + 2, vec![0x29]));
+        content.push((ebr + 3, 0x4a3b1c2du32.to_le_bytes().to_vec()));
+        content.push((ebr + 7, b"MY STICK   ".to_vec()));
+        content
+    }
+
+    fn fat_image(fat32: bool, total_sectors: u32) -> Cursor<Vec<u8>> {
+        let content = fat_boot_sector(fat32, total_sectors);
+        let content: Vec<(usize, &[u8])> = content
+            .iter()
+            .map(|(offset, bytes)| (*offset, bytes.as_slice()))
+            .collect();
+        build_image(4096, &content)
+    }
+
+    #[test]
+    fn test_probe_fat() {
+        let info = probe(&mut fat_image(false, 8000)).unwrap().unwrap();
+        assert_eq!(FilesystemType::Fat12, info.fs_type);
+        assert_eq!("vfat", info.fs_type.name());
+        assert_eq!(Some("MY STICK".to_string()), info.label);
+        assert_eq!(Some("4A3B-1C2D".to_string()), info.uuid);
+
+        let info = probe(&mut fat_image(false, 200000)).unwrap().unwrap();
+        assert_eq!(FilesystemType::Fat16, info.fs_type);
+
+        let info = probe(&mut fat_image(true, 2000000)).unwrap().unwrap();
+        assert_eq!(FilesystemType::Fat32, info.fs_type);
+        assert_eq!(Some("MY STICK".to_string()), info.label);
+        assert_eq!(Some("4A3B-1C2D".to_string()), info.uuid);
+    }
+
+    #[test]
+    fn test_probe_fat_size_overflow() {
+        // a FAT32 boot sector with a garbage FAT size must not overflow
+        let mut content = fat_boot_sector(true, 2000000);
+        content.push((0x24, 0x9000_0000u32.to_le_bytes().to_vec()));
+        let content: Vec<(usize, &[u8])> = content
+            .iter()
+            .map(|(offset, bytes)| (*offset, bytes.as_slice()))
+            .collect();
+        assert_eq!(None, probe(&mut build_image(4096, &content)).unwrap());
+    }
+
+    #[test]
+    fn test_probe_exfat() {
+        // 512 bytes per sector, 8 sectors per cluster, cluster heap at sector 16,
+        // root directory in cluster 4 => byte offset (16 + 2 * 8) * 512
+        let mut label_entry = vec![0x83, 5];
+        label_entry.extend(utf16("DATA!"));
+        let mut image = build_image(
+            32768,
+            &[
+                (0, &[0xeb, 0x76, 0x90]),
+                (3, b"EXFAT   "),
+                (88, &16u32.to_le_bytes()),
+                (96, &4u32.to_le_bytes()),
+                (100, &0x12345678u32.to_le_bytes()),
+                (108, &[9, 3]),
+                (510, &[0x55, 0xaa]),
+                (16384, &[0x81; 32]),
+                (16384 + 32, &label_entry),
+            ],
+        );
+        let info = probe(&mut image).unwrap().unwrap();
+        assert_eq!(FilesystemType::Exfat, info.fs_type);
+        assert_eq!(Some("DATA!".to_string()), info.label);
+        assert_eq!(Some("1234-5678".to_string()), info.uuid);
+    }
+
+    #[test]
+    fn test_probe_ntfs() {
+        // 512 bytes per sector, 8 sectors per cluster, MFT at cluster 2,
+        // 1024 bytes per record => $Volume at 2 * 4096 + 3 * 1024
+        let volume_record = 8192 + 3072;
+        let mut name_attribute = vec![0u8; 0x18];
+        name_attribute[0..4].copy_from_slice(&0x60u32.to_le_bytes());
+        name_attribute[0x10..0x14].copy_from_slice(&12u32.to_le_bytes());
+        name_attribute[0x14..0x16].copy_from_slice(&0x18u16.to_le_bytes());
+        name_attribute.extend(utf16("Win 10"));
+        let attribute_length = name_attribute.len() as u32;
+        name_attribute[4..8].copy_from_slice(&attribute_length.to_le_bytes());
+        let mut image = build_image(
+            16384,
+            &[
+                (3, b"NTFS    "),
+                (0x0b, &512u16.to_le_bytes()),
+                (0x0d, &[8]),
+                (0x30, &2u64.to_le_bytes()),
+                (0x40, &[0xf6]),
+                (0x48, &0x0123456789abcdefu64.to_le_bytes()),
+                (510, &[0x55, 0xaa]),
+                (volume_record, b"FILE"),
+                // update sequence at 0x30 with 3 entries, the sector ends hold the check value
+                (volume_record + 0x04, &0x30u16.to_le_bytes()),
+                (volume_record + 0x06, &3u16.to_le_bytes()),
+                (volume_record + 0x30, &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                (volume_record + 510, &[0x01, 0x00]),
+                (volume_record + 1022, &[0x01, 0x00]),
+                (volume_record + 0x14, &0x38u16.to_le_bytes()),
+                (volume_record + 0x38, &name_attribute),
+                (
+                    volume_record + 0x38 + name_attribute.len(),
+                    &0xffff_ffffu32.to_le_bytes(),
+                ),
+            ],
+        );
+        let info = probe(&mut image).unwrap().unwrap();
+        assert_eq!(FilesystemType::Ntfs, info.fs_type);
+        assert_eq!(Some("Win 10".to_string()), info.label);
+        assert_eq!(Some("0123456789ABCDEF".to_string()), info.uuid);
+    }
+
+    #[test]
+    fn test_probe_ntfs_invalid_sector_size() {
+        // with 0 bytes per sector the MFT is at offset 0 and $Volume at 3 * 1024,
+        // its update sequence must not be applied
+        for bytes_per_sector in [0u16, 300, 8192] {
+            let mut image = build_image(
+                8192,
+                &[
+                    (3, b"NTFS    "),
+                    (0x0b, &bytes_per_sector.to_le_bytes()),
+                    (0x0d, &[8]),
+                    (0x30, &2u64.to_le_bytes()),
+                    (0x40, &[0xf6]),
+                    (510, &[0x55, 0xaa]),
+                    (3072, b"FILE"),
+                    (3072 + 0x04, &0x30u16.to_le_bytes()),
+                    (3072 + 0x06, &3u16.to_le_bytes()),
+                ],
+            );
+            assert_eq!(None, probe(&mut image).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_probe_ntfs_invalid_record_size() {
+        // 0xc1 is a shift of 63, 0xd8 one of 40 (1 TiB records)
+        for record_size in [0xc1u8, 0xd8] {
+            let mut image = build_image(
+                8192,
+                &[
+                    (3, b"NTFS    "),
+                    (0x0b, &512u16.to_le_bytes()),
+                    (0x0d, &[8]),
+                    (0x30, &2u64.to_le_bytes()),
+                    (0x40, &[record_size]),
+                    (510, &[0x55, 0xaa]),
+                ],
+            );
+            assert_eq!(None, probe(&mut image).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_probe_btrfs() {
+        let mut image = build_image(
+            0x11000,
+            &[
+                (0x10020, &UUID_BYTES),
+                (0x10040, b"_BHRfS_M"),
+                (0x1012b, b"data"),
+            ],
+        );
+        let info = probe(&mut image).unwrap().unwrap();
+        assert_eq!(FilesystemType::Btrfs, info.fs_type);
+        assert_eq!(Some("data".to_string()), info.label);
+        assert_eq!(Some(UUID.to_string()), info.uuid);
+    }
+
+    #[test]
+    fn test_probe_xfs() {
+        let mut image = build_image(4096, &[(0, b"XFSB"), (32, &UUID_BYTES), (108, b"home")]);
+        let info = probe(&mut image).unwrap().unwrap();
+        assert_eq!(FilesystemType::Xfs, info.fs_type);
+        assert_eq!(Some("home".to_string()), info.label);
+        assert_eq!(Some(UUID.to_string()), info.uuid);
+    }
+
+    #[test]
+    fn test_probe_iso9660() {
+        let mut image = build_image(
+            0x9000,
+            &[
+                (0x8000, &[1]),
+                (0x8001, b"CD001"),
+                (0x8028, b"Ubuntu 24.04 LTS amd64          "),
+                (0x8000 + 813, b"2024042515434700"),
+            ],
+        );
+        let info = probe(&mut image).unwrap().unwrap();
+        assert_eq!(FilesystemType::Iso9660, info.fs_type);
+        assert_eq!(Some("Ubuntu 24.04 LTS amd64".to_string()), info.label);
+        assert_eq!(Some("2024-04-25-15-43-47-00".to_string()), info.uuid);
+    }
+
+    #[test]
+    fn test_probe_swap() {
+        let mut image = build_image(
+            8192,
+            &[
+                (0x400, &1u32.to_le_bytes()),
+                (0x40c, &UUID_BYTES),
+                (0x41c, b"swap"),
+                (4086, b"SWAPSPACE2"),
+            ],
+        );
+        let info = probe(&mut image).unwrap().unwrap();
+        assert_eq!(FilesystemType::Swap, info.fs_type);
+        assert_eq!(Some("swap".to_string()), info.label);
+        assert_eq!(Some(UUID.to_string()), info.uuid);
+    }
+
+    #[test]
+    fn test_probe_luks() {
+        let mut image = build_image(
+            4096,
+            &[
+                (0, b"LUKS\xba\xbe\x00\x02"),
+                (24, b"cryptroot"),
+                (168, UUID.as_bytes()),
+            ],
+        );
+        let info = probe(&mut image).unwrap().unwrap();
+        assert_eq!(FilesystemType::Luks2, info.fs_type);
+        assert_eq!("crypto_LUKS", info.fs_type.name());
+        assert_eq!(Some("cryptroot".to_string()), info.label);
+        assert_eq!(Some(UUID.to_string()), info.uuid);
+
+        let mut image = build_image(
+            4096,
+            &[(0, b"LUKS\xba\xbe\x00\x01"), (168, UUID.as_bytes())],
+        );
+        let info = probe(&mut image).unwrap().unwrap();
+        assert_eq!(FilesystemType::Luks1, info.fs_type);
+        assert_eq!(None, info.label);
+        assert_eq!(Some(UUID.to_string()), info.uuid);
+    }
+
+    #[test]
+    fn test_probe_unknown() {
+        assert!(probe(&mut build_image(4096, &[])).unwrap().is_none());
+        assert!(probe(&mut Cursor::new(vec![])).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_probe_gpt_disk_image() {
+        // a whole disk with a partition table is not a filesystem
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/resources/test/gptdisk.img"
+        ));
+        assert!(probe_path(path).unwrap().is_none());
+    }
+}
@@ -0,0 +1,249 @@
+use std::path::Path;
+
+use crate::fs_wrap;
+
+/// The zone model of a device, see `queue/zoned`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ZonedModel {
+    /// a regular device which can be written at any position
+    None,
+    /// zones which should be written sequentially, random writes are handled
+    /// by the drive (host-aware SMR)
+    HostAware,
+    /// zones which must be written sequentially (host-managed SMR, ZNS)
+    HostManaged,
+}
+
+/// The write cache mode of a device, see `queue/write_cache`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WriteCache {
+    /// writes are cached, the kernel sends flushes for durability
+    WriteBack,
+    /// writes are not cached or the cache is non-volatile
+    WriteThrough,
+}
+
+/// Sector sizes, I/O hints and request queue settings from `/sys/block/<dev>/queue`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueueInfo {
+    /// the smallest unit the device can address in bytes (usually 512 or 4096)
+    pub logical_block_size: u32,
+    /// the smallest unit the device can write without read-modify-write in bytes
+    pub physical_block_size: u32,
+    /// the preferred minimum I/O size in bytes, e.g. the chunk size of a RAID
+    pub minimum_io_size: u32,
+    /// the preferred I/O size for sustained I/O in bytes, 0 if not reported,
+    /// e.g. the stripe width of a RAID
+    pub optimal_io_size: u32,
+    /// whether the device is rotational (HDD) or not (SSD, NVMe, ...)
+    pub rotational: bool,
+    /// the internal allocation unit for discard in bytes
+    pub discard_granularity: u32,
+    /// the maximum number of bytes discarded by a single request, 0 if the
+    /// device does not support discard (TRIM)
+    pub discard_max_bytes: u64,
+    /// the zone model
+    pub zoned: ZonedModel,
+    /// the number of zones of a zoned device, 0 otherwise
+    pub nr_zones: u32,
+    /// the active I/O scheduler (the one in brackets), e.g. `mq-deadline` or `none`
+    pub scheduler: Option<String>,
+    /// all I/O schedulers available for the device
+    pub available_schedulers: Vec<String>,
+    /// the maximum number of queued requests
+    pub nr_requests: Option<u32>,
+    /// the read ahead in KiB
+    pub read_ahead_kb: Option<u32>,
+    /// the write cache mode
+    pub write_cache: Option<WriteCache>,
+}
+
+impl QueueInfo {
+    /// whether the device supports discard (TRIM/UNMAP)
+    pub fn supports_discard(&self) -> bool {
+        self.discard_max_bytes > 0
+    }
+
+    /// whether the device has zones which are written sequentially (SMR, ZNS)
+    pub fn is_zoned(&self) -> bool {
+        self.zoned != ZonedModel::None
+    }
+}
+
+/// Parses the scheduler file like `none [mq-deadline] kyber` into the active
+/// and the available schedulers
+pub(crate) fn parse_scheduler(value: &str) -> (Option<String>, Vec<String>) {
+    let mut active = None;
+    let mut available = vec![];
+    for scheduler in value.split_whitespace() {
+        match scheduler
+            .strip_prefix('[')
+            .and_then(|scheduler| scheduler.strip_suffix(']'))
+        {
+            Some(scheduler) => {
+                active = Some(scheduler.to_string());
+                available.push(scheduler.to_string());
+            }
+            None => available.push(scheduler.to_string()),
+        }
+    }
+    // devices without scheduler support list only `none`
+    if active.is_none() && available.len() == 1 {
+        active = available.first().cloned();
+    }
+    (active, available)
+}
+
+fn read_optional(dir: &Path, file: &str) -> Option<String> {
+    fs_wrap::read_file_to_string(&dir.join(file))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+fn read_number<T: std::str::FromStr>(dir: &Path, file: &str) -> Option<T> {
+    read_optional(dir, file)?.parse().ok()
+}
+
+/// Reads `<device_dir>/queue`, None if there is no queue (e.g. for partitions)
+pub(crate) fn read_queue_info(device_dir: &Path) -> Option<QueueInfo> {
+    let queue_dir = device_dir.join("queue");
+    let logical_block_size = read_number(&queue_dir, "logical_block_size")?;
+    let (scheduler, available_schedulers) = read_optional(&queue_dir, "scheduler")
+        .map(|scheduler| parse_scheduler(&scheduler))
+        .unwrap_or_default();
+    Some(QueueInfo {
+        logical_block_size,
+        physical_block_size: read_number(&queue_dir, "physical_block_size")
+            .unwrap_or(logical_block_size),
+        minimum_io_size: read_number(&queue_dir, "minimum_io_size").unwrap_or(logical_block_size),
+        optimal_io_size: read_number(&queue_dir, "optimal_io_size").unwrap_or(0),
+        rotational: read_optional(&queue_dir, "rotational").is_some_and(|value| value == "1"),
+        discard_granularity: read_number(&queue_dir, "discard_granularity").unwrap_or(0),
+        discard_max_bytes: read_number(&queue_dir, "discard_max_bytes").unwrap_or(0),
+        zoned: match read_optional(&queue_dir, "zoned").as_deref() {
+            Some("host-aware") => ZonedModel::HostAware,
+            Some("host-managed") => ZonedModel::HostManaged,
+            _ => ZonedModel::None,
+        },
+        nr_zones: read_number(&queue_dir, "nr_zones").unwrap_or(0),
+        scheduler,
+        available_schedulers,
+        nr_requests: read_number(&queue_dir, "nr_requests"),
+        read_ahead_kb: read_number(&queue_dir, "read_ahead_kb"),
+        write_cache: match read_optional(&queue_dir, "write_cache").as_deref() {
+            Some("write back") => Some(WriteCache::WriteBack),
+            Some("write through") => Some(WriteCache::WriteThrough),
+            _ => None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_scheduler() {
+        assert_eq!(
+            (
+                Some("mq-deadline".to_string()),
+                vec![
+                    "none".to_string(),
+                    "mq-deadline".to_string(),
+                    "kyber".to_string()
+                ]
+            ),
+            parse_scheduler("none [mq-deadline] kyber")
+        );
+        assert_eq!(
+            (Some("none".to_string()), vec!["none".to_string()]),
+            parse_scheduler("none")
+        );
+        assert_eq!((None, vec![]), parse_scheduler(""));
+    }
+
+    fn create_queue(device_dir: &Path, files: &[(&str, &str)]) {
+        let queue_dir = device_dir.join("queue");
+        fs::create_dir_all(&queue_dir).unwrap();
+        for (file, content) in files {
+            fs::write(queue_dir.join(file), format!("{}\n", content)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_read_smr_hdd() {
+        let temp_dir = tempdir().unwrap();
+        create_queue(
+            temp_dir.path(),
+            &[
+                ("logical_block_size", "512"),
+                ("physical_block_size", "4096"),
+                ("minimum_io_size", "4096"),
+                ("optimal_io_size", "0"),
+                ("rotational", "1"),
+                ("discard_granularity", "0"),
+                ("discard_max_bytes", "0"),
+                ("zoned", "host-managed"),
+                ("nr_zones", "55880"),
+                ("scheduler", "none [mq-deadline]"),
+                ("nr_requests", "64"),
+                ("read_ahead_kb", "128"),
+                ("write_cache", "write back"),
+            ],
+        );
+
+        let info = read_queue_info(temp_dir.path()).unwrap();
+        assert_eq!(512, info.logical_block_size);
+        assert_eq!(4096, info.physical_block_size);
+        assert_eq!(4096, info.minimum_io_size);
+        assert!(info.rotational);
+        assert!(!info.supports_discard());
+        assert!(info.is_zoned());
+        assert_eq!(ZonedModel::HostManaged, info.zoned);
+        assert_eq!(55880, info.nr_zones);
+        assert_eq!(Some("mq-deadline".to_string()), info.scheduler);
+        assert_eq!(Some(64), info.nr_requests);
+        assert_eq!(Some(128), info.read_ahead_kb);
+        assert_eq!(Some(WriteCache::WriteBack), info.write_cache);
+    }
+
+    #[test]
+    fn test_read_nvme_ssd() {
+        let temp_dir = tempdir().unwrap();
+        create_queue(
+            temp_dir.path(),
+            &[
+                ("logical_block_size", "4096"),
+                ("rotational", "0"),
+                ("discard_granularity", "4096"),
+                ("discard_max_bytes", "2199023255040"),
+                ("zoned", "none"),
+                ("scheduler", "[none] mq-deadline kyber bfq"),
+                ("write_cache", "write through"),
+            ],
+        );
+
+        let info = read_queue_info(temp_dir.path()).unwrap();
+        // missing hints default to the logical block size
+        assert_eq!(4096, info.physical_block_size);
+        assert_eq!(4096, info.minimum_io_size);
+        assert!(!info.rotational);
+        assert!(info.supports_discard());
+        assert_eq!(2199023255040, info.discard_max_bytes);
+        assert!(!info.is_zoned());
+        assert_eq!(0, info.nr_zones);
+        assert_eq!(Some("none".to_string()), info.scheduler);
+        assert_eq!(4, info.available_schedulers.len());
+        assert_eq!(None, info.nr_requests);
+        assert_eq!(Some(WriteCache::WriteThrough), info.write_cache);
+
+        assert_eq!(None, read_queue_info(&temp_dir.path().join("nvme0n1p1")));
+    }
+}
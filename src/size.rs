@@ -1,10 +1,44 @@
 /// A representation of a size information
-#[derive(Debug)]
+///
+/// With the feature "serde" a size is (de)serialized as `{"bytes": 1024, "blocks": 2}`,
+/// the number of 512 byte blocks is used when deserializing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "SizeSchema", from = "SizeSchema")
+)]
 pub struct Size {
     raw_size: u64,
 }
 
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SizeSchema {
+    bytes: u64,
+    blocks: u64,
+}
+
+#[cfg(feature = "serde")]
+impl From<Size> for SizeSchema {
+    fn from(size: Size) -> Self {
+        SizeSchema {
+            bytes: size.get_bytes(),
+            blocks: size.get_raw_size(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<SizeSchema> for Size {
+    fn from(schema: SizeSchema) -> Self {
+        Size::new(schema.blocks)
+    }
+}
+
 /// supported units for conversion and display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Unit {
     Blocks,
     KiloByte,
@@ -50,6 +84,16 @@ impl Size {
         Size { raw_size }
     }
 
+    /// creates a size from a value in bytes (rounded down to full 512 bytes blocks)
+    pub fn from_bytes(bytes: u64) -> Size {
+        Size::new(bytes / 512)
+    }
+
+    /// returns the size in bytes
+    pub fn get_bytes(&self) -> u64 {
+        self.raw_size * 512
+    }
+
     /// returns the raw size value which is based on a 512 bytes block size
     pub fn get_raw_size(&self) -> u64 {
         self.raw_size
@@ -83,6 +127,23 @@ mod tests {
         assert_eq!(size.get_raw_size(), 12345);
     }
 
+    #[test]
+    fn test_bytes() {
+        let size = Size::from_bytes(1048576);
+        assert_eq!(size.get_raw_size(), 2048);
+        assert_eq!(size.get_bytes(), 1048576);
+        assert_eq!(Size::from_bytes(1023).get_raw_size(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_schema() {
+        let json = serde_json::to_string(&Size::new(2)).unwrap();
+        assert_eq!(r#"{"bytes":1024,"blocks":2}"#, json);
+        let size: Size = serde_json::from_str(r#"{"bytes":1048576,"blocks":2048}"#).unwrap();
+        assert_eq!(Size::new(2048), size);
+    }
+
     #[test]
     fn test_get_size_in_unit() {
         let mut size = Size::new(8192);
@@ -0,0 +1,350 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    path::Path,
+};
+
+use crate::Device;
+
+/// Reads the names of the devices linked in a `holders` or `slaves` directory
+pub(crate) fn read_links(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Node {
+    parents: BTreeSet<String>,
+    children: BTreeSet<String>,
+}
+
+/// The dependency graph of the storage stack
+///
+/// Parents are the devices something is built on, children the devices built
+/// on top of it: a disk is the parent of its partitions, a partition is the
+/// parent of the LUKS device mapper device it holds and that is the parent of
+/// the LVM volumes on it. The edges are taken from the `holders` and `slaves`
+/// directories in sysfs as read by [`Drives::get_devices`](crate::Drives::get_devices).
+///
+/// All traversals are safe against cycles, every device is visited once.
+///
+/// ```no_run
+/// let devices = drives::get_devices().unwrap();
+/// let topology = drives::StorageTopology::new(&devices);
+/// println!("{:?}", topology.backing_devices("dm-1"));
+/// print!("{}", topology);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StorageTopology {
+    nodes: BTreeMap<String, Node>,
+}
+
+impl StorageTopology {
+    /// builds the graph from the devices with their partitions
+    pub fn new(devices: &[Device]) -> StorageTopology {
+        let mut topology = StorageTopology::default();
+        for device in devices {
+            topology.nodes.entry(device.name.clone()).or_default();
+            for holder in &device.holders {
+                topology.add_edge(&device.name, holder);
+            }
+            for slave in &device.slaves {
+                topology.add_edge(slave, &device.name);
+            }
+            for partition in &device.partitions {
+                topology.add_edge(&device.name, &partition.name);
+                for holder in &partition.holders {
+                    topology.add_edge(&partition.name, holder);
+                }
+            }
+        }
+        topology
+    }
+
+    fn add_edge(&mut self, parent: &str, child: &str) {
+        self.nodes
+            .entry(parent.to_string())
+            .or_default()
+            .children
+            .insert(child.to_string());
+        self.nodes
+            .entry(child.to_string())
+            .or_default()
+            .parents
+            .insert(parent.to_string());
+    }
+
+    /// all devices and partitions in the graph
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.nodes.keys().map(String::as_str)
+    }
+
+    /// whether the device or partition is part of the graph
+    pub fn contains(&self, name: &str) -> bool {
+        self.nodes.contains_key(name)
+    }
+
+    /// the devices the given one is directly built on (e.g. the slaves of a
+    /// device mapper device or the disk of a partition)
+    pub fn parents(&self, name: &str) -> Vec<&str> {
+        self.nodes.get(name).map_or(vec![], |node| {
+            node.parents.iter().map(String::as_str).collect()
+        })
+    }
+
+    /// the devices directly built on the given one (its holders and partitions)
+    pub fn children(&self, name: &str) -> Vec<&str> {
+        self.nodes.get(name).map_or(vec![], |node| {
+            node.children.iter().map(String::as_str).collect()
+        })
+    }
+
+    /// all devices the given one is built on, nearest first
+    pub fn ancestors(&self, name: &str) -> Vec<&str> {
+        self.walk(name, |node| &node.parents)
+    }
+
+    /// all devices built on the given one, nearest first
+    pub fn descendants(&self, name: &str) -> Vec<&str> {
+        self.walk(name, |node| &node.children)
+    }
+
+    /// the devices at the bottom of the stack below the given one, e.g. the
+    /// physical disks backing an LVM volume (the device itself if it has no parents)
+    pub fn backing_devices(&self, name: &str) -> Vec<&str> {
+        let mut backing: Vec<&str> = self
+            .ancestors(name)
+            .into_iter()
+            .filter(|ancestor| self.parents(ancestor).is_empty())
+            .collect();
+        if backing.is_empty() {
+            backing.extend(self.nodes.get_key_value(name).map(|(key, _)| key.as_str()));
+        }
+        backing.sort();
+        backing
+    }
+
+    /// the devices without parents, usually the physical disks
+    pub fn roots(&self) -> Vec<&str> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.parents.is_empty())
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    // breadth first, each node once
+    fn walk<'a>(
+        &'a self,
+        name: &str,
+        next: impl Fn(&'a Node) -> &'a BTreeSet<String>,
+    ) -> Vec<&'a str> {
+        let mut visited: BTreeSet<&str> = BTreeSet::new();
+        let mut result = vec![];
+        let mut queue: Vec<&str> = match self.nodes.get_key_value(name) {
+            Some((key, node)) => {
+                visited.insert(key.as_str());
+                next(node).iter().map(String::as_str).collect()
+            }
+            None => return result,
+        };
+        while !queue.is_empty() {
+            let mut next_queue = vec![];
+            for name in queue {
+                if !visited.insert(name) {
+                    continue;
+                }
+                result.push(name);
+                if let Some(node) = self.nodes.get(name) {
+                    next_queue.extend(next(node).iter().map(String::as_str));
+                }
+            }
+            queue = next_queue;
+        }
+        result
+    }
+
+    /// renders the given device and everything built on it as tree
+    pub fn tree(&self, name: &str) -> String {
+        let mut output = String::new();
+        if let Some((name, _)) = self.nodes.get_key_value(name) {
+            output.push_str(name);
+            output.push('\n');
+            let mut path = vec![name.as_str()];
+            self.write_children(&mut output, name, "", &mut path);
+        }
+        output
+    }
+
+    fn write_children<'a>(
+        &'a self,
+        output: &mut String,
+        name: &str,
+        indent: &str,
+        path: &mut Vec<&'a str>,
+    ) {
+        let children = self.children(name);
+        for (index, child) in children.iter().enumerate() {
+            let is_last = index + 1 == children.len();
+            output.push_str(indent);
+            output.push_str(if is_last { "└─" } else { "├─" });
+            output.push_str(child);
+            // a device already on the path from the root would repeat forever
+            if path.contains(child) {
+                output.push_str(" (cycle)\n");
+                continue;
+            }
+            output.push('\n');
+            path.push(child);
+            let child_indent = format!("{}{}", indent, if is_last { "  " } else { "│ " });
+            self.write_children(output, child, &child_indent, path);
+            path.pop();
+        }
+    }
+}
+
+/// prints the trees of all root devices
+impl fmt::Display for StorageTopology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for root in self.roots() {
+            f.write_str(&self.tree(root))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::DrivesBuilder;
+
+    fn create_device(block_dir: &Path, name: &str, holders: &[&str], slaves: &[&str]) {
+        let device_dir = block_dir.join(name);
+        fs::create_dir_all(device_dir.join("holders")).unwrap();
+        fs::create_dir_all(device_dir.join("slaves")).unwrap();
+        fs::write(device_dir.join("removable"), "0").unwrap();
+        fs::write(device_dir.join("size"), "2048").unwrap();
+        for holder in holders {
+            fs::write(device_dir.join("holders").join(holder), "").unwrap();
+        }
+        for slave in slaves {
+            fs::write(device_dir.join("slaves").join(slave), "").unwrap();
+        }
+    }
+
+    // sda2 holds LUKS (dm-0) with LVM (dm-1, dm-2) on it, sdb1 and sdc1 are
+    // mirrored by md127
+    fn read_topology() -> StorageTopology {
+        let sysfs_root = tempdir().unwrap();
+        let block_dir = sysfs_root.path().join("block");
+        create_device(&block_dir, "sda", &[], &[]);
+        create_device(&block_dir.join("sda"), "sda1", &[], &[]);
+        create_device(&block_dir.join("sda"), "sda2", &["dm-0"], &[]);
+        create_device(&block_dir, "dm-0", &["dm-1", "dm-2"], &["sda2"]);
+        create_device(&block_dir, "dm-1", &[], &["dm-0"]);
+        create_device(&block_dir, "dm-2", &[], &["dm-0"]);
+        for disk in ["sdb", "sdc"] {
+            create_device(&block_dir, disk, &[], &[]);
+            create_device(
+                &block_dir.join(disk),
+                &format!("{}1", disk),
+                &["md127"],
+                &[],
+            );
+        }
+        create_device(&block_dir, "md127", &[], &["sdb1", "sdc1"]);
+        for (partition, number) in [
+            ("sda/sda1", 1),
+            ("sda/sda2", 2),
+            ("sdb/sdb1", 1),
+            ("sdc/sdc1", 1),
+        ] {
+            fs::write(
+                block_dir.join(partition).join("partition"),
+                number.to_string(),
+            )
+            .unwrap();
+        }
+
+        let devices = DrivesBuilder::new()
+            .sysfs_root(sysfs_root.path())
+            .read_udev(false)
+            .build()
+            .get_devices()
+            .unwrap();
+        StorageTopology::new(&devices)
+    }
+
+    #[test]
+    fn test_navigation() {
+        let topology = read_topology();
+        assert_eq!(vec!["sda2"], topology.parents("dm-0"));
+        assert_eq!(vec!["dm-1", "dm-2"], topology.children("dm-0"));
+        assert_eq!(vec!["dm-0", "sda2", "sda"], topology.ancestors("dm-1"));
+        assert_eq!(vec!["sda"], topology.backing_devices("dm-1"));
+        assert_eq!(vec!["sdb", "sdc"], topology.backing_devices("md127"));
+        assert_eq!(vec!["sda"], topology.backing_devices("sda"));
+        assert_eq!(vec!["dm-0", "dm-1", "dm-2"], topology.descendants("sda2"));
+        assert_eq!(vec!["sda", "sdb", "sdc"], topology.roots());
+        assert!(topology.parents("unknown").is_empty());
+        assert!(topology.backing_devices("unknown").is_empty());
+    }
+
+    #[test]
+    fn test_tree() {
+        let topology = read_topology();
+        assert_eq!(
+            concat!(
+                "sda\n",
+                "├─sda1\n",
+                "└─sda2\n",
+                "  └─dm-0\n",
+                "    ├─dm-1\n",
+                "    └─dm-2\n",
+            ),
+            topology.tree("sda")
+        );
+        assert_eq!(
+            concat!(
+                "sda\n",
+                "├─sda1\n",
+                "└─sda2\n",
+                "  └─dm-0\n",
+                "    ├─dm-1\n",
+                "    └─dm-2\n",
+                "sdb\n",
+                "└─sdb1\n",
+                "  └─md127\n",
+                "sdc\n",
+                "└─sdc1\n",
+                "  └─md127\n",
+            ),
+            topology.to_string()
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut topology = StorageTopology::default();
+        topology.add_edge("a", "b");
+        topology.add_edge("b", "c");
+        topology.add_edge("c", "b");
+        assert_eq!(vec!["b", "c"], topology.descendants("a"));
+        assert_eq!(vec!["a", "c"], topology.ancestors("b"));
+        assert_eq!(vec!["a"], topology.backing_devices("c"));
+        assert_eq!("a\n└─b\n  └─c\n    └─b (cycle)\n", topology.tree("a"));
+    }
+}
@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{device_number::DeviceNumber, fs_wrap::read_lines};
+
+/// Properties of a block device stored by udev in `/run/udev/data/b<major>:<minor>`
+///
+/// The database is readable without elevated privileges. Only the properties
+/// found in the database are set, everything is available in
+/// [`UdevInfo::properties`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UdevInfo {
+    /// the filesystem type (ID_FS_TYPE)
+    pub fs_type: Option<String>,
+    /// the filesystem label (ID_FS_LABEL_ENC decoded, or ID_FS_LABEL)
+    pub fs_label: Option<String>,
+    /// the filesystem UUID (ID_FS_UUID)
+    pub fs_uuid: Option<String>,
+    /// the type of the partition table on a device (ID_PART_TABLE_TYPE)
+    pub part_table_type: Option<String>,
+    /// the UUID of the partition table on a device (ID_PART_TABLE_UUID)
+    pub part_table_uuid: Option<String>,
+    /// the UUID of the partition table entry (ID_PART_ENTRY_UUID)
+    pub part_entry_uuid: Option<String>,
+    /// the name of the partition table entry (ID_PART_ENTRY_NAME)
+    pub part_entry_name: Option<String>,
+    /// the type of the partition table entry, a GUID or an MBR type byte (ID_PART_ENTRY_TYPE)
+    pub part_entry_type: Option<String>,
+    /// the serial (ID_SERIAL)
+    pub serial: Option<String>,
+    /// the serial as reported by the device (ID_SERIAL_SHORT)
+    pub serial_short: Option<String>,
+    /// the bus the device is attached to, e.g. `usb`, `ata` or `nvme` (ID_BUS)
+    pub bus: Option<String>,
+    /// the model (ID_MODEL_ENC decoded, or ID_MODEL)
+    pub model: Option<String>,
+    /// the vendor (ID_VENDOR_ENC decoded, or ID_VENDOR)
+    pub vendor: Option<String>,
+    /// the world wide name (ID_WWN)
+    pub wwn: Option<String>,
+    /// the symlinks udev created below `/dev` for the device
+    pub symlinks: Vec<String>,
+    /// all properties (`E:` lines) of the database entry
+    pub properties: BTreeMap<String, String>,
+}
+
+impl UdevInfo {
+    fn from_lines(lines: impl Iterator<Item = String>) -> UdevInfo {
+        let mut info = UdevInfo::default();
+        for line in lines {
+            if let Some(symlink) = line.strip_prefix("S:") {
+                info.symlinks.push(symlink.to_owned());
+            } else if let Some((key, value)) = line
+                .strip_prefix("E:")
+                .and_then(|entry| entry.split_once('='))
+            {
+                info.properties.insert(key.to_owned(), value.to_owned());
+            }
+        }
+        info.fs_type = info.property("ID_FS_TYPE");
+        info.fs_label = info.encoded_property("ID_FS_LABEL");
+        info.fs_uuid = info.property("ID_FS_UUID");
+        info.part_table_type = info.property("ID_PART_TABLE_TYPE");
+        info.part_table_uuid = info.property("ID_PART_TABLE_UUID");
+        info.part_entry_uuid = info.property("ID_PART_ENTRY_UUID");
+        info.part_entry_name = info.property("ID_PART_ENTRY_NAME");
+        info.part_entry_type = info.property("ID_PART_ENTRY_TYPE");
+        info.serial = info.property("ID_SERIAL");
+        info.serial_short = info.property("ID_SERIAL_SHORT");
+        info.bus = info.property("ID_BUS");
+        info.model = info.encoded_property("ID_MODEL");
+        info.vendor = info.encoded_property("ID_VENDOR");
+        info.wwn = info.property("ID_WWN");
+        info
+    }
+
+    fn property(&self, key: &str) -> Option<String> {
+        self.properties
+            .get(key)
+            .filter(|value| !value.is_empty())
+            .cloned()
+    }
+
+    // udev replaces whitespace and unsafe characters in some properties, the
+    // original value is kept in a `<key>_ENC` property with `\xNN` escapes
+    fn encoded_property(&self, key: &str) -> Option<String> {
+        self.property(&format!("{}_ENC", key))
+            .map(|value| decode_hex_escapes(&value).trim().to_owned())
+            .or_else(|| self.property(key))
+    }
+}
+
+fn decode_hex_escapes(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'\\' && bytes.get(index + 1) == Some(&b'x') {
+            let code = value
+                .get(index + 2..index + 4)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+            if let Some(code) = code {
+                decoded.push(code);
+                index += 4;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// reads the udev database entry of a block device, returns `None` when there
+/// is no entry (e.g. udev is not running)
+pub fn read_udev_info(data_dir: &Path, dev: DeviceNumber) -> Option<UdevInfo> {
+    let lines = read_lines(data_dir.join(format!("b{}", dev))).ok()?;
+    Some(UdevInfo::from_lines(lines.map_while(Result::ok)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_read_udev_info() {
+        let data_dir = tempdir().unwrap();
+        let content = concat!(
+            "S:disk/by-uuid/4A3B-1C2D\n",
+            "S:disk/by-label/MY\\x20STICK\n",
+            "W:3\n",
+            "I:1234567\n",
+            "E:ID_FS_TYPE=vfat\n",
+            "E:ID_FS_LABEL=MY_STICK\n",
+            "E:ID_FS_LABEL_ENC=MY\\x20STICK\n",
+            "E:ID_FS_UUID=4A3B-1C2D\n",
+            "E:ID_PART_ENTRY_UUID=0d3c6e4e-01\n",
+            "E:ID_PART_ENTRY_TYPE=0xc\n",
+            "E:ID_SERIAL=SanDisk_Cruzer_Blade_4C530001-0:0\n",
+            "E:ID_SERIAL_SHORT=4C530001\n",
+            "E:ID_BUS=usb\n",
+            "E:ID_MODEL=Cruzer_Blade\n",
+            "E:ID_MODEL_ENC=Cruzer\\x20Blade\\x20\\x20\\x20\\x20\n",
+            "G:systemd\n",
+        );
+        fs::write(data_dir.path().join("b8:17"), content).unwrap();
+
+        let info = read_udev_info(data_dir.path(), DeviceNumber::new(8, 17)).unwrap();
+        assert_eq!(Some("vfat".to_string()), info.fs_type);
+        assert_eq!(Some("MY STICK".to_string()), info.fs_label);
+        assert_eq!(Some("4A3B-1C2D".to_string()), info.fs_uuid);
+        assert_eq!(Some("0d3c6e4e-01".to_string()), info.part_entry_uuid);
+        assert_eq!(Some("0xc".to_string()), info.part_entry_type);
+        assert_eq!(None, info.part_entry_name);
+        assert_eq!(Some("4C530001".to_string()), info.serial_short);
+        assert_eq!(Some("usb".to_string()), info.bus);
+        assert_eq!(Some("Cruzer Blade".to_string()), info.model);
+        assert_eq!(None, info.vendor);
+        assert_eq!(2, info.symlinks.len());
+        assert_eq!("disk/by-uuid/4A3B-1C2D", info.symlinks[0]);
+        assert_eq!(11, info.properties.len());
+    }
+
+    #[test]
+    fn test_missing_udev_info() {
+        let data_dir = tempdir().unwrap();
+        assert!(read_udev_info(data_dir.path(), DeviceNumber::new(8, 0)).is_none());
+    }
+
+    #[test]
+    fn test_decode_hex_escapes() {
+        assert_eq!("a b/c", decode_hex_escapes("a\\x20b\\x2fc"));
+        assert_eq!("no\\xzz", decode_hex_escapes("no\\xzz"));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{ffi::CString, mem::MaybeUninit};
+
+use crate::{error::DrivesError, Size};
+
+/// Usage statistics of a mounted filesystem (like `df` shows them)
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilesystemUsage {
+    /// total size of the filesystem
+    pub total: Size,
+    /// free space, including the space reserved for root
+    pub free: Size,
+    /// free space available to unprivileged users
+    pub available: Size,
+    /// used space
+    pub used: Size,
+    /// total number of inodes
+    pub total_inodes: u64,
+    /// number of free inodes
+    pub free_inodes: u64,
+    /// the filesystem is mounted read-only
+    pub read_only: bool,
+}
+
+/// calls statvfs(3) for the given path
+// the field types of libc::statvfs are u32 on some 32 bit targets
+#[allow(clippy::unnecessary_cast)]
+pub fn statvfs(path: &str) -> Result<FilesystemUsage, DrivesError> {
+    let statvfs_failed = || DrivesError::StatvfsFailed {
+        path: path.to_string(),
+    };
+    let c_path = CString::new(path).map_err(|_err| statvfs_failed())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path is a valid nul terminated string and stat points to
+    // memory large enough for a statvfs struct
+    let result = unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if result != 0 {
+        return Err(statvfs_failed());
+    }
+    // SAFETY: statvfs returned successfully, so the struct is initialized
+    let stat = unsafe { stat.assume_init() };
+
+    let fragment_size = stat.f_frsize as u64;
+    let blocks = stat.f_blocks as u64;
+    let free_blocks = stat.f_bfree as u64;
+    Ok(FilesystemUsage {
+        total: Size::from_bytes(blocks * fragment_size),
+        free: Size::from_bytes(free_blocks * fragment_size),
+        available: Size::from_bytes(stat.f_bavail as u64 * fragment_size),
+        used: Size::from_bytes(blocks.saturating_sub(free_blocks) * fragment_size),
+        total_inodes: stat.f_files as u64,
+        free_inodes: stat.f_ffree as u64,
+        read_only: stat.f_flag & libc::ST_RDONLY != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::{mount_options::MountOptions, DeviceNumber, Mount};
+
+    #[test]
+    fn test_usage_of_tempdir() {
+        let temp_dir = tempdir().unwrap();
+        let mount = Mount {
+            device: "tmpfs".to_string(),
+            mountpoint: temp_dir.path().to_str().unwrap().to_string(),
+            filesystem: "tmpfs".to_string(),
+            root: "/".to_string(),
+            dev: DeviceNumber::new(0, 42),
+            mount_id: 42,
+            options: MountOptions::default(),
+        };
+
+        let usage = mount.usage().unwrap();
+        assert!(usage.total.get_raw_size() > 0);
+        assert!(usage.free.get_raw_size() <= usage.total.get_raw_size());
+        assert!(usage.available.get_raw_size() <= usage.free.get_raw_size());
+        assert_eq!(
+            usage.total.get_raw_size() - usage.free.get_raw_size(),
+            usage.used.get_raw_size()
+        );
+        assert!(usage.free_inodes <= usage.total_inodes);
+        assert!(!usage.read_only);
+    }
+
+    #[test]
+    fn test_usage_of_missing_path() {
+        assert!(matches!(
+            statvfs("/does/not/exist"),
+            Err(DrivesError::StatvfsFailed { .. })
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use std::{fs, path::Path};
+
+use crate::fs_wrap;
+
+/// Details of the USB device a drive is attached by (the drive itself or its
+/// USB-to-SATA/NVMe bridge)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsbInfo {
+    /// the vendor ID (idVendor)
+    pub vendor_id: u16,
+    /// the product ID (idProduct)
+    pub product_id: u16,
+    /// the manufacturer string reported by the device
+    pub manufacturer: Option<String>,
+    /// the product string reported by the device
+    pub product: Option<String>,
+    /// the serial string reported by the device
+    pub serial: Option<String>,
+    /// the negotiated speed in Mb/s: 12 (full speed), 480 (high speed), 5000,
+    /// 10000 or 20000 (super speed), low speed (1.5 Mb/s) is reported as 1
+    pub speed: Option<u32>,
+    /// the number of the USB bus
+    pub bus: Option<u32>,
+    /// the bus and port path as used in sysfs, e.g. `2-1.4` for port 4 of the hub at port 1 of bus 2
+    pub port_path: String,
+    /// the driver of the mass storage interface, `uas` or `usb-storage`
+    pub driver: Option<String>,
+}
+
+impl UsbInfo {
+    /// whether the negotiated speed is USB 3 super speed (5 Gb/s) or faster,
+    /// false for a drive in a USB 2 port or behind a USB 2 hub
+    pub fn is_super_speed(&self) -> bool {
+        self.speed.is_some_and(|speed| speed >= 5000)
+    }
+}
+
+fn read_optional(dir: &Path, file: &str) -> Option<String> {
+    fs_wrap::read_file_to_string(&dir.join(file))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+fn read_id(dir: &Path, file: &str) -> Option<u16> {
+    u16::from_str_radix(&read_optional(dir, file)?, 16).ok()
+}
+
+fn read_usb_device(dir: &Path, driver: Option<String>) -> Option<UsbInfo> {
+    Some(UsbInfo {
+        vendor_id: read_id(dir, "idVendor")?,
+        product_id: read_id(dir, "idProduct")?,
+        manufacturer: read_optional(dir, "manufacturer"),
+        product: read_optional(dir, "product"),
+        serial: read_optional(dir, "serial"),
+        speed: read_optional(dir, "speed")
+            .and_then(|speed| speed.parse::<f64>().ok())
+            .map(|speed| speed as u32),
+        bus: read_optional(dir, "busnum").and_then(|bus| bus.parse().ok()),
+        port_path: fs_wrap::path_to_string(Path::new(dir.file_name()?)),
+        driver,
+    })
+}
+
+/// Walks up from a `/sys/block/<name>` entry through the SCSI host and the
+/// USB interface (having the driver) to the USB device (having `idVendor`)
+pub(crate) fn read_usb_info(block_entry: &Path) -> Option<UsbInfo> {
+    let device_path = fs::canonicalize(block_entry).ok()?;
+    let mut driver = None;
+    for dir in device_path.ancestors() {
+        if dir.file_name().is_none_or(|name| name == "devices") {
+            break;
+        }
+        if driver.is_none() && dir.join("bInterfaceNumber").exists() {
+            driver = fs::read_link(dir.join("driver"))
+                .ok()
+                .and_then(|target| Some(target.file_name()?.to_str()?.to_string()));
+        }
+        if dir.join("idVendor").exists() {
+            return read_usb_device(dir, driver);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use tempfile::tempdir;
+
+    const USB_DEVICE: &str = "devices/pci0000:00/0000:00:14.0/usb2/2-1";
+    const BLOCK_DEVICE: &str = "2-1:1.0/host6/target6:0:0/6:0:0:0/block/sda";
+
+    // a sysfs tree with a drive behind a USB bridge and /sys/block/sda linked into it
+    fn create_usb_drive(sysfs_root: &Path, driver: &str) -> PathBuf {
+        let usb_device = sysfs_root.join(USB_DEVICE);
+        fs::create_dir_all(usb_device.join(BLOCK_DEVICE)).unwrap();
+        for (file, content) in [
+            ("idVendor", "174c\n"),
+            ("idProduct", "55aa\n"),
+            ("manufacturer", "ASMedia\n"),
+            ("product", "ASM1153E\n"),
+            ("serial", "123456789012\n"),
+            ("speed", "480\n"),
+            ("busnum", "2\n"),
+            ("devpath", "1\n"),
+        ] {
+            fs::write(usb_device.join(file), content).unwrap();
+        }
+        let interface = usb_device.join("2-1:1.0");
+        fs::write(interface.join("bInterfaceNumber"), "00\n").unwrap();
+        std::os::unix::fs::symlink(
+            format!("../../../../../bus/usb/drivers/{}", driver),
+            interface.join("driver"),
+        )
+        .unwrap();
+
+        fs::create_dir(sysfs_root.join("block")).unwrap();
+        let block_entry = sysfs_root.join("block").join("sda");
+        std::os::unix::fs::symlink(
+            PathBuf::from("..").join(USB_DEVICE).join(BLOCK_DEVICE),
+            &block_entry,
+        )
+        .unwrap();
+        block_entry
+    }
+
+    #[test]
+    fn test_read_usb_info() {
+        let sysfs_root = tempdir().unwrap();
+        let block_entry = create_usb_drive(sysfs_root.path(), "uas");
+
+        let usb = read_usb_info(&block_entry).unwrap();
+        assert_eq!(0x174c, usb.vendor_id);
+        assert_eq!(0x55aa, usb.product_id);
+        assert_eq!(Some("ASMedia".to_string()), usb.manufacturer);
+        assert_eq!(Some("ASM1153E".to_string()), usb.product);
+        assert_eq!(Some("123456789012".to_string()), usb.serial);
+        assert_eq!(Some(480), usb.speed);
+        assert!(!usb.is_super_speed());
+        assert_eq!(Some(2), usb.bus);
+        assert_eq!("2-1", usb.port_path);
+        assert_eq!(Some("uas".to_string()), usb.driver);
+    }
+
+    #[test]
+    fn test_low_speed_and_usb_storage() {
+        let sysfs_root = tempdir().unwrap();
+        let block_entry = create_usb_drive(sysfs_root.path(), "usb-storage");
+        let usb_device = sysfs_root.path().join(USB_DEVICE);
+        fs::write(usb_device.join("speed"), "1.5\n").unwrap();
+        fs::remove_file(usb_device.join("serial")).unwrap();
+
+        let usb = read_usb_info(&block_entry).unwrap();
+        assert_eq!(Some(1), usb.speed);
+        assert_eq!(None, usb.serial);
+        assert_eq!(Some("usb-storage".to_string()), usb.driver);
+
+        fs::write(usb_device.join("speed"), "10000\n").unwrap();
+        assert!(read_usb_info(&block_entry).unwrap().is_super_speed());
+    }
+
+    #[test]
+    fn test_no_usb_parent() {
+        let sysfs_root = tempdir().unwrap();
+        let device = sysfs_root
+            .path()
+            .join("devices/pci0000:00/0000:00:17.0/ata1/host0/target0:0:0/0:0:0:0/block/sda");
+        fs::create_dir_all(&device).unwrap();
+        assert_eq!(None, read_usb_info(&device));
+    }
+}